@@ -1,30 +1,211 @@
 
 use structuralize::pattern::data::*;
 
-#[derive(Debug)]
-pub(crate) struct QuoteOpt { 
-    pub(crate) escape_char : Option<char>, 
-    pub(crate) quote_chars : Vec<char> 
+use crate::unicode;
+use crate::temporal::Temporal;
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[non_exhaustive]
+pub struct QuoteOpt { 
+    pub escape_char : Option<char>, 
+    pub quote_chars : Vec<char>,
+    // Strings which open and close with different chars, ie [10/Oct/2000:13:55:36 -0700]
+    pub pairs : Vec<(char, char)>,
 }
 
-#[derive(Debug, PartialEq)]
-pub(crate) enum Div {
+impl QuoteOpt {
+    pub fn new(quote_chars : &[char], escape_char : Option<char>) -> Self {
+        QuoteOpt { escape_char, quote_chars: quote_chars.to_vec(), pairs: vec![] }
+    }
+
+    pub(crate) fn opens(&self, c : char) -> bool {
+        self.quote_chars.contains(&c) || self.pairs.iter().any(|(open, _)| *open == c)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Div {
     EndLine,
     BlankLine,
 }
 
-#[derive(Debug)]
-pub(crate) struct RecordOpt {
-    pub(crate) record_div : Div,
-    pub(crate) field_div : Vec<char>,
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum CharClass {
+    Standard,
+    Ascii,
+    Unicode,
+}
+
+impl CharClass {
+    pub(crate) fn is_space(&self, c : char) -> bool {
+        match self {
+            CharClass::Standard => c.is_whitespace(),
+            CharClass::Ascii => c.is_ascii_whitespace(),
+            CharClass::Unicode => unicode::is_pattern_white_space(c),
+        }
+    }
+
+    pub(crate) fn is_number(&self, c : char) -> bool {
+        match self {
+            CharClass::Standard => c.is_numeric(),
+            CharClass::Ascii => c.is_ascii_digit(),
+            CharClass::Unicode => unicode::is_decimal_digit(c),
+        }
+    }
+
+    pub(crate) fn is_symbol_start(&self, c : char) -> bool {
+        match self {
+            CharClass::Standard => c.is_alphabetic() || c == '_',
+            CharClass::Ascii => c.is_ascii_alphabetic() || c == '_',
+            CharClass::Unicode => unicode::is_xid_start(c) || c == '_',
+        }
+    }
+
+    pub(crate) fn is_symbol(&self, c : char) -> bool {
+        match self {
+            CharClass::Standard => c.is_alphanumeric() || c == '_',
+            CharClass::Ascii => c.is_ascii_alphanumeric() || c == '_',
+            CharClass::Unicode => unicode::is_xid_continue(c),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Spacing {
+    Drop,
+    Chars,
+    Runs,
+    Trivia,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[non_exhaustive]
+pub struct KeywordOpt {
+    pub trues : Vec<String>,
+    pub falses : Vec<String>,
+    pub nulls : Vec<String>,
+    pub others : Vec<String>,
+    pub case_sensitive : bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Duplicates {
+    First,
+    Last,
+    All,
+    Error,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[non_exhaustive]
+pub struct KeyValueOpt {
+    pub separators : Vec<char>,
+    pub duplicates : Duplicates,
+}
+
+impl KeyValueOpt {
+    pub fn new(separators : &[char], duplicates : Duplicates) -> Self {
+        KeyValueOpt { separators: separators.to_vec(), duplicates }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum EmptyRecords {
+    Keep,
+    Drop,
+    Error,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[non_exhaustive]
+pub struct RecordOpt {
+    pub record_div : Div,
+    pub field_div : Vec<char>,
+    pub sub_field_div : Vec<Vec<char>>,
+    pub collapse_div : bool,
+    pub empty : EmptyRecords,
+    pub blank_whitespace_lines : bool,
+    // A field divider at the end of a line still starts an (empty) field, ie a,b,
+    pub trailing_empty_field : bool,
+    // A CR before the endline is part of the line ending, ie \r\n
+    pub crlf_endlines : bool,
+    // Tables have rule lines along with leading and trailing field dividers, ie | a | b |
+    pub table : bool,
+    // Columns are sliced at boundaries inferred from the positions of the header words
+    pub aligned : bool,
+    // Lines starting with the open char name the section holding the records below them, ie [core]
+    pub sections : Option<(char, char)>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Numbers {
+    Off,
+    Digits,
+    // Optional sign, fraction and exponent, ie -1.5e3
+    Decimal,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Trim {
+    Keep,
+    Start,
+    End,
+    Both,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum Column {
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum ColumnRule {
+    Raw(bool),
+    Quotes(Vec<char>),
+    Escape(Option<char>),
+    Numbers(Numbers),
+    Trim(Trim),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Default)]
+#[non_exhaustive]
+pub struct Limits {
+    pub string_length : Option<usize>,
+    pub values_per_field : Option<usize>,
+    pub fields_per_record : Option<usize>,
+    pub records : Option<usize>,
+    pub depth : Option<usize>,
+    pub input_size : Option<usize>,
+}
+
+// The option types are non_exhaustive so that adding a knob does not break callers.
+// Build them from their defaults and constructors, then set the public fields.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[non_exhaustive]
 pub struct Options {
-    pub(crate) strings : Option<QuoteOpt>,
-    pub(crate) record : RecordOpt,
-    pub(crate) preserve_spacing : bool,
-    pub(crate) endline : char,
+    pub strings : Option<QuoteOpt>,
+    pub record : RecordOpt,
+    pub spacing : Spacing,
+    pub endline : char,
+    pub brackets : Vec<(char, char)>,
+    pub operators : Vec<String>,
+    pub chars : CharClass,
+    pub temporals : bool,
+    pub keywords : KeywordOpt,
+    // The header record is parsed raw and trimmed, so its fields are strings even
+    // when the records below it are tokenized, ie "First Name" rather than two symbols
+    pub has_header : bool,
+    pub key_value : Option<KeyValueOpt>,
+    pub limits : Limits,
+    pub raw : bool,
+    pub numbers : Numbers,
+    pub trim : Trim,
+    pub columns : Vec<(Column, ColumnRule)>,
+    // Lines starting with one of these are skipped
+    pub comments : Vec<char>,
+    // Names the columns of a Document instead of, or without, a header record
+    pub column_names : Vec<String>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -32,6 +213,9 @@ pub enum Entry {
     Record(Vec<Entry>),
     List(Vec<Entry>),
     Field(Vec<Entry>),
+    Group(Vec<Entry>),
+    Trivia(Vec<Entry>),
+    Section(Vec<Entry>),
     Value(Value),
 }
 
@@ -57,12 +241,93 @@ impl Entry {
         }
     }
 
+    pub fn from_group<'a>(&'a self) -> Result<&'a [Entry], String> {
+        match self {
+            Entry::Group(xs) => Ok(xs),
+            x => Err(format!("Expected Entry::Group but found: {:?}", x)),
+        }
+    }
+
+    pub fn from_trivia<'a>(&'a self) -> Result<&'a [Entry], String> {
+        match self {
+            Entry::Trivia(xs) => Ok(xs),
+            x => Err(format!("Expected Entry::Trivia but found: {:?}", x)),
+        }
+    }
+
+    pub fn from_section<'a>(&'a self) -> Result<&'a [Entry], String> {
+        match self {
+            Entry::Section(xs) => Ok(xs),
+            x => Err(format!("Expected Entry::Section but found: {:?}", x)),
+        }
+    }
+
     pub fn from_value<'a>(&'a self) -> Result<&'a Value, String> {
         match self {
             Entry::Value(x) => Ok(x),
             x => Err(format!("Expected Entry::Value but found: {:?}", x)),
         }
     }
+
+    pub fn lookup<'a>(&'a self, key : &str) -> Result<Vec<&'a Entry>, String> {
+        let fields = self.from_record()?[0].from_list()?;
+        Ok(fields.iter().filter_map(|f| match f {
+            Entry::Field(kv) if kv.len() == 2 && kv[0].text().trim() == key => Some(&kv[1]),
+            _ => None,
+        }).collect())
+    }
+
+    pub fn text(&self) -> String {
+        match self {
+            Entry::Value(x) => x.to_string(),
+            Entry::Record(xs) | Entry::List(xs) | Entry::Field(xs) | Entry::Group(xs) | Entry::Trivia(xs) | Entry::Section(xs) => 
+                xs.iter().map(|x| x.text()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Document {
+    pub header : Option<Vec<String>>,
+    pub records : Vec<Entry>,
+    // From the alignment row of a markdown table, ie |:--|:-:|--:|
+    pub alignment : Option<Vec<Align>>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Align {
+    Default,
+    Left,
+    Center,
+    Right,
+}
+
+impl Document {
+    pub fn rows<'a>(&'a self) -> impl Iterator<Item = Row<'a>> {
+        let header = self.header.as_deref().unwrap_or(&[]);
+        self.records.iter().map(move |record| Row { header, record })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Row<'a> {
+    header : &'a [String],
+    record : &'a Entry,
+}
+
+impl<'a> Row<'a> {
+    pub fn record(&self) -> &'a Entry {
+        self.record
+    }
+
+    pub fn fields(&self) -> Result<&'a [Entry], String> {
+        self.record.from_record()?[0].from_list()
+    }
+
+    pub fn get(&self, name : &str) -> Result<&'a Entry, String> {
+        let index = self.header.iter().position(|x| x == name).ok_or_else(|| format!("No column named '{}'", name))?;
+        self.fields()?.get(index).ok_or_else(|| format!("No field for column '{}'", name))
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -71,27 +336,268 @@ pub enum Value {
     Symbol(String),
     Number(String),
     Space(char),
+    Whitespace(String),
     Punct(char),
+    Operator(String),
+    Temporal(Temporal),
+    Bool(bool),
+    Null,
+    Keyword(String),
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::String(x) | Value::Symbol(x) | Value::Number(x) | Value::Whitespace(x) | Value::Operator(x) | Value::Keyword(x) => write!(f, "{}", x),
+            Value::Space(x) | Value::Punct(x) => write!(f, "{}", x),
+            Value::Temporal(x) => write!(f, "{}", x.text),
+            Value::Bool(x) => write!(f, "{}", x),
+            Value::Null => Ok(()),
+        }
+    }
 }
 
 impl Default for Options {
     fn default() -> Self {
-        Options { strings: Some(QuoteOpt { escape_char: Some('\\'), quote_chars: vec!['\'', '"'] } ) 
-                , preserve_spacing: false
+        Options { strings: Some(QuoteOpt { escape_char: Some('\\'), quote_chars: vec!['\'', '"'], pairs: vec![] } ) 
+                , spacing: Spacing::Drop
                 , endline: '\n' 
-                , record: RecordOpt { field_div: vec![','], record_div: Div::EndLine }
+                , brackets: vec![]
+                , operators: vec![]
+                , chars: CharClass::Standard
+                , temporals: false
+                , keywords: KeywordOpt::default()
+                , has_header: false
+                , key_value: None
+                , limits: Limits::default()
+                , raw: false
+                , numbers: Numbers::Digits
+                , trim: Trim::Keep
+                , columns: vec![]
+                , comments: vec![]
+                , column_names: vec![]
+                , record: RecordOpt::default()
                 }
     }
 }
 
+impl Default for RecordOpt {
+    fn default() -> Self {
+        RecordOpt { field_div: vec![','] 
+                  , sub_field_div: vec![]
+                  , collapse_div: false
+                  , empty: EmptyRecords::Keep
+                  , blank_whitespace_lines: false
+                  , trailing_empty_field: false
+                  , crlf_endlines: false
+                  , table: false
+                  , aligned: false
+                  , sections: None
+                  , record_div: Div::EndLine 
+                  }
+    }
+}
+
+impl Default for KeywordOpt {
+    fn default() -> Self {
+        KeywordOpt { trues: vec![], falses: vec![], nulls: vec![], others: vec![], case_sensitive: true }
+    }
+}
+
 impl Options {
+    pub(crate) fn is_divider(&self, c : char) -> bool {
+        c == self.endline 
+            || self.record.field_div.contains(&c) 
+            || self.record.sub_field_div.iter().any(|x| x.contains(&c))
+    }
+
+    // Reports every char which has more than one role, naming the role which
+    // parse_records checks first and so shadows the others.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        // In the order that parse_records checks them
+        let mut roles : Vec<(String, char)> = vec![];
+        if self.record.record_div == Div::EndLine {
+            roles.push(("endline".into(), self.endline));
+        }
+        roles.extend(self.record.field_div.iter().map(|c| ("field divider".into(), *c)));
+        for (level, dividers) in self.record.sub_field_div.iter().enumerate() {
+            roles.extend(dividers.iter().map(|c| (format!("sub field divider level {}", level + 1), *c)));
+        }
+        if let Some(kv) = &self.key_value {
+            roles.extend(kv.separators.iter().map(|c| ("key value separator".into(), *c)));
+        }
+        if let Some(strings) = &self.strings {
+            roles.extend(strings.quote_chars.iter().map(|c| ("quote char".into(), *c)));
+            roles.extend(strings.pairs.iter().map(|(open, _)| ("quote char".into(), *open)));
+        }
+
+        let mut errors = vec![];
+        for (i, (role, c)) in roles.iter().enumerate() {
+            if let Some((earlier, _)) = roles[..i].iter().find(|(r, x)| x == c && r != role) {
+                errors.push(format!("{:?} as {} is shadowed by {:?} as {}", c, role, c, earlier));
+            }
+        }
+
+        // An escape char which is also a quote char escapes by doubling
+        if let Some(QuoteOpt { escape_char: Some(e), quote_chars, .. }) = &self.strings {
+            if !quote_chars.contains(e) {
+                if let Some((role, _)) = roles.iter().find(|(_, x)| x == e) {
+                    errors.push(format!("{:?} as escape char is also used as {}", e, role));
+                }
+            }
+        }
+
+        // Operators are only tried after the other kinds of value, so one starting with
+        // any of their chars is never lexed, ie ",," with ',' as a field divider
+        for operator in &self.operators {
+            let c = match operator.chars().next() {
+                Some(c) => c,
+                None => {
+                    errors.push("\"\" as operator is empty".to_string());
+                    continue;
+                },
+            };
+            let role = match roles.iter().find(|(_, x)| *x == c) {
+                Some((role, _)) => Some(role.as_str()),
+                None if self.chars.is_space(c) => Some("spacing"),
+                None if self.chars.is_number(c) => Some("number"),
+                None if self.chars.is_symbol_start(c) => Some("symbol"),
+                None if self.brackets.iter().any(|(open, close)| *open == c || *close == c) => Some("bracket"),
+                None => None,
+            };
+            if let Some(role) = role {
+                errors.push(format!("{:?} as operator is shadowed by {:?} as {}", operator, c, role));
+            }
+        }
+
+        if errors.len() == 0 {
+            Ok(())
+        }
+        else {
+            Err(errors)
+        }
+    }
+
+    pub fn validated(self) -> Result<Self, String> {
+        match self.validate() {
+            Ok(()) => Ok(self),
+            Err(errors) => Err(format!("Invalid options: {}", errors.join("; "))),
+        }
+    }
+
+    // RFC 4180, quotes are escaped by doubling them and spacing is part of the field
+    pub fn csv() -> Self {
+        Options::default()
+            .field_dividers(&[','])
+            .allow_strings_with_escape(&['"'], '"')
+            .preserve_spacing_runs()
+            .trailing_empty_fields(true)
+            .crlf_endlines(true)
+    }
+
+    pub fn tsv() -> Self {
+        Options::default()
+            .field_dividers(&['\t'])
+            .disallow_strings()
+            .preserve_spacing_runs()
+            .trailing_empty_fields(true)
+            .crlf_endlines(true)
+    }
+
+    // For files like /etc/passwd and /etc/group
+    pub fn colon_separated() -> Self {
+        Options::default()
+            .field_dividers(&[':'])
+            .disallow_strings()
+            .preserve_spacing_runs()
+            .trailing_empty_fields(true)
+            .crlf_endlines(true)
+    }
+
+    pub fn logfmt() -> Self {
+        Options::default()
+            .field_dividers(&[' ', '\t'])
+            .collapse_dividers(true)
+            .key_values(&['='])
+            .allow_strings_with_escape(&['"'], '\\')
+    }
+
+    // For the output of commands like ps and df
+    pub fn whitespace_columns() -> Self {
+        Options::default()
+            .field_dividers(&[' ', '\t'])
+            .collapse_dividers(true)
+            .disallow_strings()
+    }
+
+    // One field per line with records separated by blank lines
+    pub fn paragraphs() -> Self {
+        Options::default()
+            .multi_line_records()
+            .field_dividers(&['\n'])
+            .whitespace_lines_are_blank(true)
+            .disallow_strings()
+            .preserve_spacing_runs()
+    }
+
+    // Markdown pipe tables as well as ascii tables bordered with +---+ rule lines
+    pub fn markdown_table() -> Self {
+        Options::default()
+            .field_dividers(&['|'])
+            .tables(true)
+            .has_header(true)
+            .disallow_strings()
+            .raw_fields(true)
+            .trim(Trim::Both)
+    }
+
+    // Also for .desktop and git config files, repeated sections are merged
+    pub fn ini() -> Self {
+        Options::default()
+            .field_dividers(&[])
+            .key_values(&['='])
+            .sections('[', ']')
+            .comments(&['#', ';'])
+            .allow_strings_with_escape(&['"'], '\\')
+            .raw_fields(true)
+            .trim(Trim::Both)
+            .whitespace_lines_are_blank(true)
+            .drop_empty_records()
+    }
+
+    // Apache and Nginx access logs
+    pub fn common_log() -> Self {
+        Options::default()
+            .field_dividers(&[' '])
+            .collapse_dividers(true)
+            .allow_strings_with_escape(&['"'], '\\')
+            .quote_pairs(&[('[', ']')])
+            .raw_fields(true)
+            .column_names(&["host", "ident", "user", "time", "request", "status", "bytes"])
+    }
+
+    pub fn combined_log() -> Self {
+        Options::common_log()
+            .column_names(&["host", "ident", "user", "time", "request", "status", "bytes", "referer", "user-agent"])
+    }
+
     pub fn endline(mut self, endline : char) -> Self {
         self.endline = endline;
         self
     }
 
     pub fn preserve_spacing(mut self, preserve : bool) -> Self {
-        self.preserve_spacing = preserve;
+        self.spacing = if preserve { Spacing::Chars } else { Spacing::Drop };
+        self
+    }
+
+    pub fn preserve_spacing_runs(mut self) -> Self {
+        self.spacing = Spacing::Runs;
+        self
+    }
+
+    pub fn preserve_spacing_as_trivia(mut self) -> Self {
+        self.spacing = Spacing::Trivia;
         self
     }
 
@@ -99,6 +605,16 @@ impl Options {
         self.record.field_div = dividers.to_vec();
         self
     }
+
+    pub fn sub_field_dividers(mut self, levels : &[&[char]]) -> Self {
+        self.record.sub_field_div = levels.iter().map(|x| x.to_vec()).collect();
+        self
+    }
+
+    pub fn collapse_dividers(mut self, collapse : bool) -> Self {
+        self.record.collapse_div = collapse;
+        self
+    }
     
     pub fn single_line_records(mut self) -> Self {
         self.record.record_div = Div::EndLine;
@@ -110,13 +626,75 @@ impl Options {
         self
     }
 
+    pub fn keep_empty_records(mut self) -> Self {
+        self.record.empty = EmptyRecords::Keep;
+        self
+    }
+
+    pub fn drop_empty_records(mut self) -> Self {
+        self.record.empty = EmptyRecords::Drop;
+        self
+    }
+
+    pub fn reject_empty_records(mut self) -> Self {
+        self.record.empty = EmptyRecords::Error;
+        self
+    }
+
+    pub fn whitespace_lines_are_blank(mut self, blank : bool) -> Self {
+        self.record.blank_whitespace_lines = blank;
+        self
+    }
+
+    pub fn trailing_empty_fields(mut self, trailing : bool) -> Self {
+        self.record.trailing_empty_field = trailing;
+        self
+    }
+
+    pub fn crlf_endlines(mut self, crlf : bool) -> Self {
+        self.record.crlf_endlines = crlf;
+        self
+    }
+
+    pub fn tables(mut self, table : bool) -> Self {
+        self.record.table = table;
+        self
+    }
+
+    pub fn aligned_columns(mut self, aligned : bool) -> Self {
+        self.record.aligned = aligned;
+        self
+    }
+
+    pub fn sections(mut self, open : char, close : char) -> Self {
+        self.record.sections = Some((open, close));
+        self
+    }
+
+    pub fn no_sections(mut self) -> Self {
+        self.record.sections = None;
+        self
+    }
+
+    pub fn comments(mut self, comments : &[char]) -> Self {
+        self.comments = comments.to_vec();
+        self
+    }
+
     pub fn allow_strings(mut self, quotes : &[char]) -> Self {
-        self.strings = Some(QuoteOpt { escape_char: None, quote_chars: quotes.to_vec() });
+        self.strings = Some(QuoteOpt { escape_char: None, quote_chars: quotes.to_vec(), pairs: vec![] });
         self
     }
 
     pub fn allow_strings_with_escape(mut self, quotes : &[char], escape_char : char) -> Self {
-        self.strings = Some(QuoteOpt { escape_char: Some(escape_char), quote_chars: quotes.to_vec() });
+        self.strings = Some(QuoteOpt { escape_char: Some(escape_char), quote_chars: quotes.to_vec(), pairs: vec![] });
+        self
+    }
+
+    // Allows strings even without quote chars
+    pub fn quote_pairs(mut self, pairs : &[(char, char)]) -> Self {
+        let strings = self.strings.take().unwrap_or(QuoteOpt { escape_char: None, quote_chars: vec![], pairs: vec![] });
+        self.strings = Some(QuoteOpt { pairs: pairs.to_vec(), ..strings });
         self
     }
 
@@ -124,6 +702,172 @@ impl Options {
         self.strings = None;
         self
     }
+
+    pub fn brackets(mut self, pairs : &[(char, char)]) -> Self {
+        self.brackets = pairs.to_vec();
+        self
+    }
+
+    pub fn operators(mut self, operators : &[&str]) -> Self {
+        self.operators = operators.iter().map(|x| x.to_string()).collect();
+        self
+    }
+
+    pub fn standard_chars(mut self) -> Self {
+        self.chars = CharClass::Standard;
+        self
+    }
+
+    pub fn ascii_chars(mut self) -> Self {
+        self.chars = CharClass::Ascii;
+        self
+    }
+
+    pub fn unicode_chars(mut self) -> Self {
+        self.chars = CharClass::Unicode;
+        self
+    }
+
+    pub fn recognize_temporals(mut self, recognize : bool) -> Self {
+        self.temporals = recognize;
+        self
+    }
+
+    pub fn bool_keywords(mut self, trues : &[&str], falses : &[&str]) -> Self {
+        self.keywords.trues = trues.iter().map(|x| x.to_string()).collect();
+        self.keywords.falses = falses.iter().map(|x| x.to_string()).collect();
+        self
+    }
+
+    pub fn null_keywords(mut self, nulls : &[&str]) -> Self {
+        self.keywords.nulls = nulls.iter().map(|x| x.to_string()).collect();
+        self
+    }
+
+    pub fn keywords(mut self, keywords : &[&str]) -> Self {
+        self.keywords.others = keywords.iter().map(|x| x.to_string()).collect();
+        self
+    }
+
+    pub fn case_sensitive_keywords(mut self, case_sensitive : bool) -> Self {
+        self.keywords.case_sensitive = case_sensitive;
+        self
+    }
+
+    pub fn column_names(mut self, names : &[&str]) -> Self {
+        self.column_names = names.iter().map(|x| x.to_string()).collect();
+        self
+    }
+
+    // See Options::has_header for how the header record itself is parsed
+    pub fn has_header(mut self, has_header : bool) -> Self {
+        self.has_header = has_header;
+        self
+    }
+
+    pub fn key_values(mut self, separators : &[char]) -> Self {
+        let duplicates = self.key_value.take().map_or(Duplicates::All, |kv| kv.duplicates);
+        self.key_value = Some(KeyValueOpt { separators: separators.to_vec(), duplicates });
+        self
+    }
+
+    pub fn no_key_values(mut self) -> Self {
+        self.key_value = None;
+        self
+    }
+
+    pub fn keep_first_duplicate_keys(self) -> Self {
+        self.duplicate_keys(Duplicates::First)
+    }
+
+    pub fn keep_last_duplicate_keys(self) -> Self {
+        self.duplicate_keys(Duplicates::Last)
+    }
+
+    pub fn keep_all_duplicate_keys(self) -> Self {
+        self.duplicate_keys(Duplicates::All)
+    }
+
+    pub fn reject_duplicate_keys(self) -> Self {
+        self.duplicate_keys(Duplicates::Error)
+    }
+
+    pub fn max_string_length(mut self, max : usize) -> Self {
+        self.limits.string_length = Some(max);
+        self
+    }
+
+    pub fn max_values_per_field(mut self, max : usize) -> Self {
+        self.limits.values_per_field = Some(max);
+        self
+    }
+
+    pub fn max_fields_per_record(mut self, max : usize) -> Self {
+        self.limits.fields_per_record = Some(max);
+        self
+    }
+
+    pub fn max_records(mut self, max : usize) -> Self {
+        self.limits.records = Some(max);
+        self
+    }
+
+    pub fn max_depth(mut self, max : usize) -> Self {
+        self.limits.depth = Some(max);
+        self
+    }
+
+    pub fn max_input_size(mut self, max : usize) -> Self {
+        self.limits.input_size = Some(max);
+        self
+    }
+
+    // Each field becomes a single string of its text with quotes removed and escapes applied
+    pub fn raw_fields(mut self, raw : bool) -> Self {
+        self.raw = raw;
+        self
+    }
+
+    pub fn numbers(mut self, numbers : Numbers) -> Self {
+        self.numbers = numbers;
+        self
+    }
+
+    pub fn trim(mut self, trim : Trim) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    pub fn column(mut self, column : Column, rules : &[ColumnRule]) -> Self {
+        self.columns.extend(rules.iter().map(|rule| (column.clone(), rule.clone())));
+        self
+    }
+
+    pub(crate) fn apply_column_rule(&mut self, rule : &ColumnRule) {
+        match rule {
+            ColumnRule::Raw(raw) => { self.raw = *raw; },
+            ColumnRule::Quotes(quote_chars) => {
+                self.strings = match (quote_chars.len(), self.strings.take()) {
+                    (0, _) => None,
+                    (_, Some(strings)) => Some(QuoteOpt { quote_chars: quote_chars.clone(), ..strings }),
+                    (_, None) => Some(QuoteOpt { quote_chars: quote_chars.clone(), escape_char: None, pairs: vec![] }),
+                };
+            },
+            ColumnRule::Escape(escape_char) => {
+                if let Some(strings) = self.strings.as_mut() {
+                    strings.escape_char = *escape_char;
+                }
+            },
+            ColumnRule::Numbers(numbers) => { self.numbers = *numbers; },
+            ColumnRule::Trim(trim) => { self.trim = *trim; },
+        }
+    }
+
+    fn duplicate_keys(mut self, duplicates : Duplicates) -> Self {
+        let separators = self.key_value.take().map_or(vec!['='], |kv| kv.separators);
+        self.key_value = Some(KeyValueOpt { separators, duplicates });
+        self
+    }
 }
 
 impl Matchable for Entry {
@@ -135,6 +879,9 @@ impl Matchable for Entry {
             Entry::List(ls) => MatchKind::List(ls),
             Entry::Record(xs) => MatchKind::Cons("Record".into(), xs),
             Entry::Field(values) => MatchKind::Cons("Field".into(), values),
+            Entry::Group(xs) => MatchKind::Cons("Group".into(), xs),
+            Entry::Trivia(xs) => MatchKind::Cons("Trivia".into(), xs),
+            Entry::Section(xs) => MatchKind::Cons("Section".into(), xs),
             Entry::Value(value) => MatchKind::Atom(value),
         }
     }
@@ -143,6 +890,9 @@ impl Matchable for Entry {
         match self {
             Entry::Record(xs) => Pattern::Cons { name: "Record".into(), params: xs.iter().map(|x| x.to_pattern()).collect() },
             Entry::Field(values) => Pattern::Cons { name: "Field".into(), params: values.iter().map(|x| x.to_pattern()).collect() },
+            Entry::Group(xs) => Pattern::Cons { name: "Group".into(), params: xs.iter().map(|x| x.to_pattern()).collect() },
+            Entry::Trivia(xs) => Pattern::Cons { name: "Trivia".into(), params: xs.iter().map(|x| x.to_pattern()).collect() },
+            Entry::Section(xs) => Pattern::Cons { name: "Section".into(), params: xs.iter().map(|x| x.to_pattern()).collect() },
             Entry::Value(value) => Pattern::Atom(value.clone()),
             Entry::List(l) => Pattern::ExactList(l.iter().map(|x| x.to_pattern()).collect()),
         }
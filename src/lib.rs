@@ -1,4 +1,9 @@
 
 pub mod data;
 pub mod parsing;
+pub mod reading;
+pub mod sniffing;
+pub mod spec;
+pub mod temporal;
+mod unicode;
 
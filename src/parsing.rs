@@ -1,265 +1,1177 @@
 
-use std::iter::Peekable;
+use std::collections::VecDeque;
 
 use crate::data::*;
+use crate::temporal;
 
 pub fn parse_records(input : &mut impl Iterator<Item = char>, options : &Options) -> Result<Entry, String> {
-    let mut input = input.peekable();
+    let (records, _) = parse_limited(input, options)?;
+    Ok(Entry::List(records))
+}
 
-    let mut records = vec![];
-    let mut fields = vec![];
-    let mut values = vec![];
+fn parse_limited(input : &mut impl Iterator<Item = char>, options : &Options) -> Result<(Vec<Entry>, Option<Vec<Align>>), String> {
+    let mut input = Input::new(input, options.limits.input_size);
+
+    let ret = if options.record.aligned {
+        parse_aligned(&mut input, options).map(|records| (records, None))
+    }
+    else {
+        parse_input(&mut input, options)
+    };
+
+    match input.exceeded {
+        Some(at) => Err(format!("Limit exceeded: maximum input size of {} chars at {}", options.limits.input_size.unwrap(), at)),
+        None => ret,
+    }
+}
+
+fn parse_input(input : &mut Input<impl Iterator<Item = char>>, options : &Options) -> Result<(Vec<Entry>, Option<Vec<Align>>), String> {
+    let mut builder = Builder::new(options);
+    let mut alignment = None;
+    // Records before the first section header stay at the top level
+    let mut top = vec![];
+    let mut sections : Vec<(Vec<Entry>, Vec<Entry>)> = vec![];
+    let mut current : Option<usize> = None;
 
     let mut last_was_endline = false;
+    let mut line_started = false;
+    // With trailing_empty_field a divider at the end of a line still starts an (empty) field
+    let mut after_divider = false;
+    // Column rules do not apply to the header, which is needed to resolve column names
+    let mut header_pending = options.has_header;
+    let header_options = header_options(options);
+    let mut columns = if header_pending { vec![] } else { resolve_columns(options, None)? };
 
     loop { 
-        let p = input.peek();
+        if header_pending && builder.records.len() != 0 {
+            columns = resolve_columns(options, Some(&builder.records[0]))?;
+            header_pending = false;
+        }
+        let column = match columns.iter().find(|(i, _)| *i == builder.fields.len()) {
+            _ if header_pending => &header_options,
+            Some((_, x)) => x,
+            None => options,
+        };
+        // CRLF line endings end lines just like a bare endline
+        if options.record.crlf_endlines && options.endline == '\n' && input.peek() == Some(&'\r') && input.peek_at(1) == Some(&'\n') {
+            input.next();
+        }
+        let at = input.location();
         if options.record.record_div == Div::BlankLine {
-            if last_was_endline && p == Some(&options.endline) {
-                if values.len() != 0 {
-                    let vs = std::mem::replace(&mut values, vec![]);
-                    fields.push(field(vs));
+            if last_was_endline && blank_line_ahead(input, options) {
+                if builder.has_values() {
+                    builder.end_field(column);
                 }
-                let fs = std::mem::replace(&mut fields, vec![]);
-                records.push(record(fs));
-                input.next();
+                builder.end_record(options, at)?;
+                skip_line(input, options);
                 continue;
             }
-            else if p == Some(&options.endline) {
+            last_was_endline = input.peek() == Some(&options.endline);
+        }
+        if input.at_line_start(options.endline) && (options.comments.len() != 0 || options.record.sections.is_some()) {
+            let first = first_on_line(input, options);
+            if first.map_or(false, |x| options.comments.contains(&x)) {
+                skip_line(input, options);
                 last_was_endline = true;
+                continue;
+            }
+            match options.record.sections {
+                Some((open, close)) if first == Some(open) => {
+                    if builder.has_values() {
+                        builder.end_field(column);
+                    }
+                    if builder.fields.len() != 0 {
+                        builder.end_record(options, at)?;
+                    }
+                    let name = parse_section_header(input, options, close)?;
+                    let records = std::mem::replace(&mut builder.records, vec![]);
+                    match current {
+                        Some(i) => { sections[i].1.extend(records); },
+                        None => { top = records; },
+                    }
+                    // A repeated section continues where it left off
+                    let key = Entry::List(name.clone()).text();
+                    current = match sections.iter().position(|(n, _)| Entry::List(n.clone()).text() == key) {
+                        Some(i) => Some(i),
+                        None => { 
+                            sections.push((name, vec![]));
+                            Some(sections.len() - 1)
+                        },
+                    };
+                    last_was_endline = true;
+                    continue;
+                },
+                _ => { },
+            }
+        }
+        if options.record.table && !line_started {
+            if rule_line_ahead(input, options) {
+                let rule = take_while(input, |x| x != options.endline);
+                // Only the rule below the header of a markdown table has alignment
+                if alignment.is_none() && builder.records.len() == 1 && rule.contains('|') {
+                    alignment = Some(parse_alignment(&rule, options));
+                }
+                input.next();
+                continue;
             }
-            else {
-                last_was_endline = false;
+            take_while(input, |x| options.chars.is_space(x) && x != options.endline);
+            if input.peek().map_or(false, |x| options.record.field_div.contains(x)) {
+                input.next();
             }
         }
+        // The trailing divider of a table row does not start another field
+        let table_end = options.record.table 
+            && input.peek().map_or(false, |x| options.record.field_div.contains(x)) 
+            && rest_of_line_blank(input, options);
+        // An escaped divider in a table cell is part of the cell, ie | x \| y |
+        let escaped_divider = options.record.table
+            && input.peek() == Some(&'\\')
+            && input.peek_at(1).map_or(false, |x| options.record.field_div.contains(x));
+        // A comment char after spacing comments out the rest of the line, ie a = 1 ; note
+        let inline_comment = input.last.map_or(false, |x| options.chars.is_space(x))
+            && input.peek().map_or(false, |x| options.comments.contains(x));
+        let p = input.peek();
+        if p.is_some() && p != Some(&options.endline) {
+            line_started = true;
+        }
         match p {
             Some(x) if options.record.record_div == Div::EndLine && *x == options.endline => { 
-                if values.len() != 0 {
-                    let vs = std::mem::replace(&mut values, vec![]);
-                    fields.push(field(vs));
+                if builder.has_values() || after_divider {
+                    builder.end_field(column);
                 }
-                let fs = std::mem::replace(&mut fields, vec![]);
-                records.push(record(fs));
+                builder.end_record(options, at)?;
+                line_started = false;
+                after_divider = false;
+                input.next();
+            },
+            Some(_) if escaped_divider => {
+                input.next();
+                builder.values.push(Entry::Value(Value::Punct(input.next().unwrap())));
+            },
+            Some(_) if inline_comment => {
+                take_while(input, |x| x != options.endline);
+            },
+            Some(_) if table_end => {
+                builder.end_field(column);
+                after_divider = false;
                 input.next();
+                take_while(input, |x| options.chars.is_space(x) && x != options.endline);
             },
             Some(x) if options.record.field_div.contains(&x) => { 
-                let vs = std::mem::replace(&mut values, vec![]);
-                fields.push(field(vs));
+                if !options.record.collapse_div || builder.has_values() {
+                    builder.end_field(column);
+                }
+                after_divider = options.record.trailing_empty_field && !options.record.collapse_div && options.record.record_div == Div::EndLine;
                 input.next();
             },
-            Some(x) if options.preserve_spacing && x.is_whitespace() => { values.push(Entry::Value(Value::Space(*x))); input.next(); },
-            Some(x) if x.is_whitespace() => { input.next(); },
-            Some(x) if x.is_numeric() => { values.push(parse_number(&mut input)); },
-            Some(x) if x.is_alphabetic() || *x == '_' => { values.push(parse_symbol(&mut input)); },
-            Some(x) if options.strings.is_some() && options.strings.as_ref().unwrap().quote_chars.contains(&x) => 
-                match options.strings.as_ref().unwrap() {
-                    QuoteOpt { escape_char: None, quote_chars } => { values.push(parse_string(&mut input, |_| false, |x| quote_chars.contains(&x))?); },
-                    QuoteOpt { escape_char: Some(escape_char), quote_chars } => { values.push(parse_string(&mut input, |x| x == *escape_char, |x| quote_chars.contains(&x))?); },
-                },
-            Some(x) => { values.push(Entry::Value(Value::Punct(*x))); input.next(); },
+            Some(x) if options.record.sub_field_div.iter().any(|d| d.contains(x)) => {
+                let x = *x;
+                let level = options.record.sub_field_div.iter().position(|d| d.contains(&x)).unwrap();
+                builder.end_part(level, column);
+                input.next();
+            },
+            Some(x) if builder.key.is_none() && options.key_value.as_ref().map_or(false, |kv| kv.separators.contains(x)) => {
+                builder.end_key(column);
+                input.next();
+            },
+            Some(_) if column.raw => { parse_raw(input, column, &mut builder.values)?; },
+            Some(_) => { parse_value(input, column, &mut builder.values, 0)?; },
             None => {
-                if values.len() != 0 {
-                    let vs = std::mem::replace(&mut values, vec![]);
-                    fields.push(field(vs));
+                if builder.has_values() || after_divider {
+                    builder.end_field(column);
                 }
-                if fields.len() != 0 {
-                    let fs = std::mem::replace(&mut fields, vec![]);
-                    records.push(record(fs));
+                if builder.fields.len() != 0 || (options.record.record_div == Div::EndLine && line_started) {
+                    builder.end_record(options, at)?;
                 }
                 break;
             },
         }
+        check_limits(&builder, options, at)?;
     }
 
-    Ok(Entry::List(records))
-}
-
-fn record(fields : Vec<Entry>) -> Entry {
-    Entry::Record(vec![Entry::List(fields)])
-}
+    if options.record.sections.is_none() {
+        return Ok((builder.records, alignment));
+    }
 
-fn field(values : Vec<Entry>) -> Entry {
-    Entry::Field(vec![Entry::List(values)])
+    match current {
+        Some(i) => { sections[i].1.extend(builder.records); },
+        None => { top = builder.records; },
+    }
+    top.extend(sections.into_iter().map(|(name, records)| section(name, records)));
+    Ok((top, alignment))
 }
 
-fn take_while(input : &mut Peekable<impl Iterator<Item = char>>, mut p : impl FnMut(char) -> bool) -> String {
-    let mut cs = vec![];
-
-    while let Some(c) = input.peek() {
-        if p(*c) {
-            cs.push(input.next().unwrap());
+// Aligned columns need every line before any can be sliced into fields, the
+// contents of each field are then parsed as usual.
+fn parse_aligned(input : &mut Input<impl Iterator<Item = char>>, options : &Options) -> Result<Vec<Entry>, String> {
+    // Dividers play no part once the lines are sliced
+    let options = &Options { record: RecordOpt { field_div: vec![], sub_field_div: vec![], ..options.record.clone() }
+                           , key_value: None
+                           , ..options.clone() 
+                           };
+
+    let text = take_while(input, |_| true);
+    let lines = text.split(options.endline)
+        .enumerate()
+        .map(|(i, x)| (i + 1, x.strip_suffix('\r').unwrap_or(x).chars().collect::<Vec<_>>()))
+        .filter(|(_, x)| !x.iter().all(|c| options.chars.is_space(*c)))
+        .collect::<Vec<_>>();
+    let bounds = column_bounds(&lines.iter().map(|(_, x)| x.as_slice()).collect::<Vec<_>>(), options);
+
+    let mut builder = Builder::new(options);
+    let mut header_pending = options.has_header;
+    let header_options = header_options(options);
+    let mut columns = if header_pending { vec![] } else { resolve_columns(options, None)? };
+
+    for (line, cs) in &lines {
+        if header_pending && builder.records.len() != 0 {
+            columns = resolve_columns(options, Some(&builder.records[0]))?;
+            header_pending = false;
         }
-        else {
-            break;
+        let at = Position { line: *line, column: 1 };
+        for (i, start) in bounds.iter().enumerate() {
+            let column = match columns.iter().find(|(x, _)| *x == i) {
+                _ if header_pending => &header_options,
+                Some((_, x)) => x,
+                None => options,
+            };
+            let end = bounds.get(i + 1).map_or(cs.len(), |x| cs.len().min(*x));
+            let cell = if *start < end { &cs[*start..end] } else { &[] };
+
+            let mut cell = Input::new(cell.iter().copied(), None);
+            while cell.peek().is_some() {
+                if column.raw {
+                    parse_raw(&mut cell, column, &mut builder.values)?;
+                }
+                else {
+                    parse_value(&mut cell, column, &mut builder.values, 0)?;
+                }
+            }
+            builder.end_field(column);
+            check_limits(&builder, options, at)?;
         }
+        builder.end_record(options, at)?;
     }
 
-    cs.into_iter().collect()
+    Ok(builder.records)
 }
 
-fn parse_number(input : &mut Peekable<impl Iterator<Item = char>>) -> Entry {
-    Entry::Value(Value::Number(take_while(input, |x| x.is_numeric())))
+// With a header each column starts at a header word, moved left to the nearest
+// gap which is blank in every line so that right aligned values fit.  Without a
+// header every such gap starts a column.
+fn column_bounds(lines : &[&[char]], options : &Options) -> Vec<usize> {
+    let is_space = |c : &char| options.chars.is_space(*c);
+    let blank = |p : usize| lines.iter().all(|x| x.get(p).map_or(true, is_space));
+
+    let mut ret = vec![];
+    match lines.first() {
+        Some(header) if options.has_header => {
+            // End of the previous header word
+            let mut last = None;
+            for p in 0..header.len() {
+                if is_space(&header[p]) {
+                    continue;
+                }
+                match last {
+                    None => { ret.push(0); },
+                    Some(last) if is_space(&header[p - 1]) => {
+                        if let Some(b) = (last + 1..=p).rev().find(|b| blank(b - 1)) {
+                            ret.push(b);
+                        }
+                    },
+                    Some(_) => { },
+                }
+                last = Some(p + 1);
+            }
+        },
+        _ => {
+            let width = lines.iter().map(|x| x.len()).max().unwrap_or(0);
+            ret.extend((0..width).filter(|p| (*p == 0 || blank(p - 1)) && !blank(*p)));
+        },
+    }
+
+    if ret.len() == 0 {
+        ret.push(0);
+    }
+    ret
 }
 
-fn parse_symbol(input : &mut Peekable<impl Iterator<Item = char>>) -> Entry {
-    Entry::Value(Value::Symbol(take_while(input, |x| x.is_alphanumeric() || x == '_')))
+pub fn parse_document(input : &mut impl Iterator<Item = char>, options : &Options) -> Result<Document, String> {
+    let (records, alignment) = parse_limited(input, options)?;
+
+    if !options.has_header && options.column_names.len() == 0 {
+        return Ok(Document { header: None, records, alignment });
+    }
+
+    let mut records = records.into_iter();
+    if options.has_header {
+        let header = records.next().ok_or_else(|| "Missing header record".to_string())?;
+        if options.column_names.len() == 0 {
+            let names = header.from_record()?[0].from_list()?.iter().map(|x| x.text().trim().to_string()).collect::<Vec<_>>();
+            return document(names, records.collect(), alignment);
+        }
+    }
+    document(options.column_names.clone(), records.collect(), alignment)
 }
 
-fn parse_string( input : &mut impl Iterator<Item = char> 
-               , mut is_escape : impl FnMut(char) -> bool
-               , mut is_end : impl FnMut(char) -> bool) 
-               -> Result<Entry, String> {
+fn document(names : Vec<String>, records : Vec<Entry>, alignment : Option<Vec<Align>>) -> Result<Document, String> {
 
-    input.next(); // Get rid of initial quote
-   
-    let mut ret = vec![];
-    let mut escape = None;
-    loop {
-        match input.next() {
-            None => { return Err("String encountered end of input".into()); },
-            Some(x) if escape.is_some() && is_end(x) => { ret.push(x); escape = None; },
-            Some(x) if escape.is_some() && is_escape(x) => { ret.push(x); escape = None; },
-            Some(x) if escape.is_some() => { ret.push(escape.unwrap()); ret.push(x); escape = None; },
-            Some(x) if is_end(x) => { break; },
-            Some(x) if is_escape(x) => { escape = Some(x); },
-            Some(x) => { ret.push(x); },
+    for (i, name) in names.iter().enumerate() {
+        if name.len() == 0 {
+            return Err(format!("Missing header name for column {}", i + 1));
+        }
+        if let Some(j) = names[..i].iter().position(|x| x == name) {
+            return Err(format!("Duplicate header name '{}' for columns {} and {}", name, j + 1, i + 1));
         }
     }
 
-    Ok(Entry::Value(Value::String(ret.into_iter().collect())))
+    for (i, record) in records.iter().enumerate() {
+        let count = record.from_record()?[0].from_list()?.len();
+        if count != names.len() {
+            return Err(format!("Record {} has {} fields but the header has {}", i + 1, count, names.len()));
+        }
+    }
+
+    Ok(Document { header: Some(names), records, alignment })
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+// The header is parsed raw so that column names keep their text, ie "First Name"
+fn header_options(options : &Options) -> Options {
+    Options { raw: true, trim: Trim::Both, ..options.clone() }
+}
 
-    use std::collections::HashMap;
+// Resolves the column rules to the Options used for each column they apply to.
+// Without a header there are no column names to resolve.
+fn resolve_columns(options : &Options, header : Option<&Entry>) -> Result<Vec<(usize, Options)>, String> {
+    let names = match header {
+        _ if options.column_names.len() != 0 => options.column_names.clone(),
+        Some(header) => header.from_record()?[0].from_list()?.iter().map(|x| x.text().trim().to_string()).collect(),
+        None => vec![],
+    };
+
+    let mut ret : Vec<(usize, Options)> = vec![];
+    for (column, rule) in &options.columns {
+        let index = match column {
+            Column::Index(index) => *index,
+            Column::Name(name) => names.iter().position(|x| x == name).ok_or_else(|| format!("No column named '{}'", name))?,
+        };
+        let i = match ret.iter().position(|(x, _)| *x == index) {
+            Some(i) => i,
+            None => {
+                ret.push((index, Options { columns: vec![], ..options.clone() }));
+                ret.len() - 1
+            },
+        };
+        ret[i].1.apply_column_rule(rule);
+    }
+    Ok(ret)
+}
 
-    use structuralize::pattern::check::*;
-    use structuralize::pattern::data::*;
-    use structuralize::pattern::matcher::*;
+#[derive(Debug, Clone, Copy)]
+struct Position {
+    line : usize,
+    column : usize,
+}
 
-    fn num(input : u32) -> Value {
-        Value::Number(format!("{}", input))
+impl std::fmt::Display for Position {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
     }
+}
 
-    fn precord(p : Pattern<Value>) -> Pattern<Value> {
-        Pattern::Cons { name: "Record".into(), params: vec![p] }
+struct Input<I : Iterator<Item = char>> {
+    input : I,
+    buffer : VecDeque<char>,
+    line : usize,
+    column : usize,
+    limit : Option<usize>,
+    pulled : usize,
+    exceeded : Option<Position>,
+    // The last char taken, which tells a custom endline apart from a '\n'
+    last : Option<char>,
+}
+
+impl<I : Iterator<Item = char>> Input<I> {
+    fn new(input : I, limit : Option<usize>) -> Self {
+        Input { input, buffer: VecDeque::new(), line: 1, column: 1, limit, pulled: 0, exceeded: None, last: None }
     }
 
-    fn pfield(p : Pattern<Value>) -> Pattern<Value> {
-        Pattern::Cons { name: "Field".into(), params: vec![p] }
+    // Once the limit is reached the input appears to end and the overflow is
+    // recorded so that the caller can report it in place of whatever error the
+    // early end of input caused.
+    fn pull(&mut self) -> Option<char> {
+        match self.limit {
+            Some(limit) if self.pulled == limit => {
+                if self.exceeded.is_none() && self.input.next().is_some() {
+                    self.exceeded = Some(self.location());
+                }
+                None
+            },
+            _ => {
+                let c = self.input.next()?;
+                self.pulled += 1;
+                Some(c)
+            },
+        }
     }
 
-    fn pexact_list(ps : Vec<Pattern<Value>>) -> Pattern<Value> {
-        Pattern::ExactList(ps)
+    fn peek(&mut self) -> Option<&char> {
+        self.peek_at(0)
     }
 
-    fn plist_path(ps : Vec<Pattern<Value>>) -> Pattern<Value> {
-        Pattern::ListPath(ps)
+    fn peek_at(&mut self, index : usize) -> Option<&char> {
+        while self.buffer.len() <= index {
+            let c = self.pull()?;
+            self.buffer.push_back(c);
+        }
+        self.buffer.get(index)
     }
 
-    fn m<'a>(p : Pattern<Value>, d : &'a Entry) -> Vec<HashMap<Box<str>, &'a Entry>> {
-        let tc = check_pattern(p).unwrap();
-        pattern_match(&tc, d).map(|x| x.into_iter().collect::<HashMap<_, _>>()).collect::<Vec<_>>()
+    fn location(&self) -> Position {
+        Position { line: self.line, column: self.column }
     }
 
-    fn p_empty_record() -> Pattern<Value> {
-            plist_path(vec![precord(pexact_list(vec![]))])
+    fn at_line_start(&self, endline : char) -> bool {
+        self.last.map_or(true, |x| x == endline)
     }
+}
 
-    #[test]
-    fn parse_records_should_parse_multi_line_records_with_space() {
-        let mut input = "1 2\n3 4\n\n\n5 6\n7 8".chars();
-        let output = parse_records(&mut input, &Options::default().multi_line_records().field_dividers(&['\n']).preserve_spacing(true)).unwrap();
+impl<I : Iterator<Item = char>> Iterator for Input<I> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = match self.buffer.pop_front() {
+            Some(c) => c,
+            None => self.pull()?,
+        };
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        }
+        else {
+            self.column += 1;
+        }
+        self.last = Some(c);
+        Some(c)
+    }
+}
 
-        let filled_pattern = plist_path(
-            vec![precord(
-                pexact_list(vec![Pattern::CaptureVar("line_one".into()), Pattern::CaptureVar("line_two".into())]))]);
-        let filled_records = m(filled_pattern, &output);
+fn check_limits(builder : &Builder, options : &Options, at : Position) -> Result<(), String> {
+    check_limit(options.limits.values_per_field, builder.values.len(), "values per field", at)?;
+    check_limit(options.limits.fields_per_record, builder.fields.len(), "fields per record", at)
+}
 
-        assert_eq!(filled_records.len(), 2);
+fn check_limit(max : Option<usize>, count : usize, description : &str, at : Position) -> Result<(), String> {
+    match max {
+        Some(max) if count > max => Err(format!("Limit exceeded: maximum of {} {} at {}", max, description, at)),
+        _ => Ok(()),
+    }
+}
 
-        let filled_internal_pattern = pfield(pexact_list(vec![ Pattern::CaptureVar("a".into())
-                                                             , Pattern::CaptureVar("b".into())
-                                                             , Pattern::CaptureVar("c".into())
-                                                             ]));
+fn blank_line_ahead(input : &mut Input<impl Iterator<Item = char>>, options : &Options) -> bool {
+    let mut i = 0;
+    loop {
+        match input.peek_at(i) {
+            Some(x) if *x == options.endline => { return true; },
+            Some('\r') if options.endline == '\n' => { i += 1; },
+            Some(x) if options.record.blank_whitespace_lines && options.chars.is_space(*x) => { i += 1; },
+            _ => { return false; },
+        }
+    }
+}
 
-        // Filled record 1
-        let filled = filled_records[0].get("line_one").unwrap();
+// A line made of dividers and rules, ie |---|:--:| or +---+---+
+// Every cell of a rule is a run of '-' or '=', with the colons of an alignment row
+// around it, at least three chars long so that a row like | - | - | is still data.
+fn rule_line_ahead(input : &mut Input<impl Iterator<Item = char>>, options : &Options) -> bool {
+    let mut line = String::new();
+    let mut i = 0;
+    while let Some(x) = input.peek_at(i) {
+        if *x == options.endline {
+            break;
+        }
+        line.push(*x);
+        i += 1;
+    }
 
-        let filled_results = m(filled_internal_pattern.clone(), filled);
+    let is_rule = |cell : &str| {
+        let run = cell.strip_prefix(':').unwrap_or(cell);
+        let run = run.strip_suffix(':').unwrap_or(run);
+        cell.chars().count() >= 3 && run.len() != 0 && run.chars().all(|x| x == '-' || x == '=')
+    };
+    let cells = line.split(|x : char| x == '+' || options.record.field_div.contains(&x))
+        .map(|x| x.trim_matches(|c : char| options.chars.is_space(c)))
+        .filter(|x| x.len() != 0)
+        .collect::<Vec<_>>();
+
+    cells.len() != 0 && cells.iter().all(|x| is_rule(x))
+}
 
-        assert_eq!(filled_results.len(), 1);
-        assert_eq!(filled_results[0].get("a").unwrap(), &&Entry::Value(num(1)));
-        assert_eq!(filled_results[0].get("b").unwrap(), &&Entry::Value(Value::Space(' ')));
-        assert_eq!(filled_results[0].get("c").unwrap(), &&Entry::Value(num(2)));
+fn rest_of_line_blank(input : &mut Input<impl Iterator<Item = char>>, options : &Options) -> bool {
+    let mut i = 1;
+    loop {
+        match input.peek_at(i) {
+            None => { return true; },
+            Some(x) if *x == options.endline => { return true; },
+            Some(x) if options.chars.is_space(*x) => { i += 1; },
+            _ => { return false; },
+        }
+    }
+}
 
-        let filled = filled_records[0].get("line_two").unwrap();
+fn parse_alignment(rule : &str, options : &Options) -> Vec<Align> {
+    rule.split(|x| x == '+' || options.record.field_div.contains(&x))
+        .map(|x| x.trim())
+        .filter(|x| x.len() != 0)
+        .map(|x| match (x.starts_with(':'), x.ends_with(':')) {
+            (true, true) => Align::Center,
+            (true, false) => Align::Left,
+            (false, true) => Align::Right,
+            (false, false) => Align::Default,
+        })
+        .collect()
+}
 
-        let filled_results = m(filled_internal_pattern.clone(), filled);
+fn first_on_line(input : &mut Input<impl Iterator<Item = char>>, options : &Options) -> Option<char> {
+    let mut i = 0;
+    loop {
+        match input.peek_at(i) {
+            Some(x) if *x != options.endline && options.chars.is_space(*x) => { i += 1; },
+            x => { return x.copied(); },
+        }
+    }
+}
 
-        assert_eq!(filled_results.len(), 1);
-        assert_eq!(filled_results[0].get("a").unwrap(), &&Entry::Value(num(3)));
-        assert_eq!(filled_results[0].get("b").unwrap(), &&Entry::Value(Value::Space(' ')));
-        assert_eq!(filled_results[0].get("c").unwrap(), &&Entry::Value(num(4)));
+fn parse_section_header(input : &mut Input<impl Iterator<Item = char>>, options : &Options, close : char) -> Result<Vec<Entry>, String> {
+    take_while(input, |x| options.chars.is_space(x) && x != options.endline);
+    let start = input.location();
+    input.next(); // Get rid of open char
+    let name = take_while(input, |x| x != close && x != options.endline);
+    if input.peek() != Some(&close) {
+        return Err(format!("Unterminated section header at {}", start));
+    }
+    input.next();
+    let rest = take_while(input, |x| x != options.endline);
+    let rest = rest.trim();
+    if rest.len() != 0 && !rest.starts_with(|x| options.comments.contains(&x)) {
+        return Err(format!("Unexpected '{}' after section header at {}", rest, start));
+    }
+    input.next();
 
-        // Filled record 2
-        let filled = filled_records[1].get("line_one").unwrap();
+    let mut values = vec![];
+    let mut name = Input::new(name.trim().chars(), None);
+    while name.peek().is_some() {
+        if options.raw {
+            parse_raw(&mut name, options, &mut values)?;
+        }
+        else {
+            parse_value(&mut name, options, &mut values, 0)?;
+        }
+    }
+    Ok(finish_values(values, options))
+}
 
-        let filled_results = m(filled_internal_pattern.clone(), filled);
+fn skip_line(input : &mut Input<impl Iterator<Item = char>>, options : &Options) {
+    while let Some(x) = input.next() {
+        if x == options.endline {
+            break;
+        }
+    }
+}
 
-        assert_eq!(filled_results.len(), 1);
-        assert_eq!(filled_results[0].get("a").unwrap(), &&Entry::Value(num(5)));
-        assert_eq!(filled_results[0].get("b").unwrap(), &&Entry::Value(Value::Space(' ')));
-        assert_eq!(filled_results[0].get("c").unwrap(), &&Entry::Value(num(6)));
+struct Builder {
+    records : Vec<Entry>,
+    fields : Vec<Entry>,
+    key : Option<Vec<Entry>>,
+    parts : Vec<Vec<Entry>>,
+    values : Vec<Entry>,
+}
 
-        let filled = filled_records[1].get("line_two").unwrap();
+impl Builder {
+    fn new(options : &Options) -> Self {
+        let parts = options.record.sub_field_div.iter().map(|_| vec![]).collect();
+        Builder { records: vec![], fields: vec![], key: None, parts, values: vec![] }
+    }
 
-        let filled_results = m(filled_internal_pattern, filled);
+    fn has_values(&self) -> bool {
+        self.values.len() != 0 || self.key.is_some() || self.parts.iter().any(|x| x.len() != 0)
+    }
 
-        assert_eq!(filled_results.len(), 1);
-        assert_eq!(filled_results[0].get("a").unwrap(), &&Entry::Value(num(7)));
-        assert_eq!(filled_results[0].get("b").unwrap(), &&Entry::Value(Value::Space(' ')));
-        assert_eq!(filled_results[0].get("c").unwrap(), &&Entry::Value(num(8)));
+    // Ends the item currently being built at sub field level `level` along with
+    // every deeper level nested inside of it.
+    fn end_part(&mut self, level : usize, options : &Options) {
+        let vs = finish_values(std::mem::replace(&mut self.values, vec![]), options);
+        let mut item = Entry::List(vs);
+        for j in (level..self.parts.len()).rev() {
+            self.parts[j].push(item);
+            if j == level {
+                break;
+            }
+            let ps = std::mem::replace(&mut self.parts[j], vec![]);
+            item = Entry::List(ps);
+        }
+    }
 
-        // Empty Record
-        let empty_records = m(p_empty_record(), &output);
-        assert_eq!(empty_records.len(), 1);
+    fn take_parts(&mut self, options : &Options) -> Vec<Entry> {
+        if self.parts.len() == 0 {
+            finish_values(std::mem::replace(&mut self.values, vec![]), options)
+        }
+        else {
+            self.end_part(0, options);
+            std::mem::replace(&mut self.parts[0], vec![])
+        }
     }
 
-    #[test]
-    fn parse_records_should_parse_multi_line_records_with_multi_blank_lines() {
-        let mut input = "1 2\n3 4\n\n\n5 6\n7 8".chars();
-        let output = parse_records(&mut input, &Options::default().multi_line_records().field_dividers(&['\n'])).unwrap();
+    fn end_key(&mut self, options : &Options) {
+        let key = self.take_parts(options);
+        self.key = Some(key);
+    }
 
-        let filled_pattern = plist_path(
-            vec![precord(
-                pexact_list(vec![Pattern::CaptureVar("line_one".into()), Pattern::CaptureVar("line_two".into())]))]);
-        let filled_records = m(filled_pattern, &output);
+    fn end_field(&mut self, options : &Options) {
+        let vs = self.take_parts(options);
+        let f = match (&options.key_value, self.key.take()) {
+            (None, _) => field(vs),
+            (Some(_), Some(key)) => key_value_field(key, vs),
+            (Some(_), None) => key_value_field(vs, vec![]),
+        };
+        self.fields.push(f);
+    }
 
-        assert_eq!(filled_records.len(), 2);
+    fn end_record(&mut self, options : &Options, at : Position) -> Result<(), String> {
+        check_limit(options.limits.fields_per_record, self.fields.len(), "fields per record", at)?;
+        let fs = std::mem::replace(&mut self.fields, vec![]);
+        if is_blank(&fs, options) {
+            match options.record.empty {
+                EmptyRecords::Keep => { },
+                EmptyRecords::Drop => { return Ok(()); },
+                EmptyRecords::Error => { return Err(format!("Empty record at line {}", at.line)); },
+            }
+        }
+        let fs = match &options.key_value {
+            Some(kv) => apply_duplicate_keys(fs, &kv.duplicates, self.records.len() + 1)?,
+            None => fs,
+        };
+        self.records.push(record(fs));
+        check_limit(options.limits.records, self.records.len(), "records", at)
+    }
+}
 
-        let filled_internal_pattern = pfield(pexact_list(vec![ Pattern::CaptureVar("a".into())
-                                                             , Pattern::CaptureVar("b".into())
-                                                             ]));
+fn is_blank(entries : &[Entry], options : &Options) -> bool {
+    fn is_spacing(entry : &Entry, options : &Options) -> bool {
+        match entry {
+            Entry::Value(Value::Space(_)) | Entry::Value(Value::Whitespace(_)) => true,
+            // Raw fields turn spacing into strings
+            Entry::Value(Value::String(x)) if options.raw => x.chars().all(|c| options.chars.is_space(c)),
+            Entry::Value(_) => false,
+            Entry::Record(xs) | Entry::List(xs) | Entry::Field(xs) | Entry::Group(xs) | Entry::Trivia(xs) | Entry::Section(xs) => xs.iter().all(|x| is_spacing(x, options)),
+        }
+    }
 
-        // Filled record 1
-        let filled = filled_records[0].get("line_one").unwrap();
+    entries.len() == 0 || (options.record.blank_whitespace_lines && entries.iter().all(|x| is_spacing(x, options)))
+}
 
-        let filled_results = m(filled_internal_pattern.clone(), filled);
+fn apply_duplicate_keys(fields : Vec<Entry>, duplicates : &Duplicates, number : usize) -> Result<Vec<Entry>, String> {
+    let keys = fields.iter().map(|f| match f {
+        Entry::Field(kv) => kv[0].text().trim().to_string(),
+        _ => unreachable!(),
+    }).collect::<Vec<_>>();
 
-        assert_eq!(filled_results.len(), 1);
-        assert_eq!(filled_results[0].get("a").unwrap(), &&Entry::Value(num(1)));
-        assert_eq!(filled_results[0].get("b").unwrap(), &&Entry::Value(num(2)));
+    let keep = |i : usize| match duplicates {
+        Duplicates::All => Ok(true),
+        Duplicates::First => Ok(!keys[..i].contains(&keys[i])),
+        Duplicates::Last => Ok(!keys[i + 1..].contains(&keys[i])),
+        Duplicates::Error if keys[..i].contains(&keys[i]) => Err(format!("Duplicate key '{}' in record {}", keys[i], number)),
+        Duplicates::Error => Ok(true),
+    };
 
-        let filled = filled_records[0].get("line_two").unwrap();
+    let mut ret = vec![];
+    for (i, f) in fields.into_iter().enumerate() {
+        if keep(i)? {
+            ret.push(f);
+        }
+    }
+    Ok(ret)
+}
 
-        let filled_results = m(filled_internal_pattern.clone(), filled);
+fn record(fields : Vec<Entry>) -> Entry {
+    Entry::Record(vec![Entry::List(fields)])
+}
 
-        assert_eq!(filled_results.len(), 1);
-        assert_eq!(filled_results[0].get("a").unwrap(), &&Entry::Value(num(3)));
-        assert_eq!(filled_results[0].get("b").unwrap(), &&Entry::Value(num(4)));
+fn section(name : Vec<Entry>, records : Vec<Entry>) -> Entry {
+    Entry::Section(vec![Entry::List(name), Entry::List(records)])
+}
+
+fn field(values : Vec<Entry>) -> Entry {
+    Entry::Field(vec![Entry::List(values)])
+}
+
+fn finish_values(values : Vec<Entry>, options : &Options) -> Vec<Entry> {
+    let values = trim(values, options.trim);
+    if options.raw {
+        vec![Entry::Value(Value::String(values.iter().map(|x| x.text()).collect()))]
+    }
+    else {
+        attach_trivia(values, options)
+    }
+}
+
+fn trim(mut values : Vec<Entry>, trim : Trim) -> Vec<Entry> {
+    fn is_space(entry : &Entry) -> bool {
+        matches!(entry, Entry::Value(Value::Space(_)) | Entry::Value(Value::Whitespace(_)))
+    }
+
+    if trim == Trim::End || trim == Trim::Both {
+        while values.last().map_or(false, is_space) {
+            values.pop();
+        }
+    }
+    if trim == Trim::Start || trim == Trim::Both {
+        let start = values.iter().position(|x| !is_space(x)).unwrap_or(values.len());
+        values.drain(..start);
+    }
+    values
+}
+
+fn attach_trivia(values : Vec<Entry>, options : &Options) -> Vec<Entry> {
+    fn is_space(entry : &Entry) -> bool {
+        matches!(entry, Entry::Value(Value::Whitespace(_)))
+    }
+
+    if options.spacing != Spacing::Trivia || values.iter().all(is_space) {
+        return values;
+    }
+
+    let mut ret = vec![];
+    let mut leading = String::new();
+    for value in values {
+        match value {
+            Entry::Value(Value::Whitespace(w)) => { leading.push_str(&w); },
+            value => {
+                let l = std::mem::replace(&mut leading, String::new());
+                ret.push(Entry::Trivia(vec![ Entry::Value(Value::Whitespace(l))
+                                           , value
+                                           , Entry::Value(Value::Whitespace(String::new()))
+                                           ]));
+            },
+        }
+    }
+
+    if leading.len() != 0 {
+        if let Some(Entry::Trivia(last)) = ret.last_mut() {
+            last[2] = Entry::Value(Value::Whitespace(leading));
+        }
+    }
+
+    ret
+}
+
+fn key_value_field(key : Vec<Entry>, values : Vec<Entry>) -> Entry {
+    Entry::Field(vec![Entry::List(key), Entry::List(values)])
+}
+
+fn group(open : char, items : Vec<Entry>, close : char) -> Entry {
+    Entry::Group(vec![Entry::Value(Value::Punct(open)), Entry::List(items), Entry::Value(Value::Punct(close))])
+}
+
+fn parse_value(input : &mut Input<impl Iterator<Item = char>>, options : &Options, values : &mut Vec<Entry>, depth : usize) -> Result<(), String> {
+    if options.temporals {
+        if let Some(temporal) = parse_temporal(input, options) {
+            values.push(temporal);
+            return Ok(());
+        }
+    }
+    // A sign only belongs to a number at the start of a field, so that 1-2 is not 1 and -2
+    if options.numbers == Numbers::Decimal 
+        && matches!(input.peek(), Some('+') | Some('-')) 
+        && input.peek_at(1).map_or(false, |x| options.chars.is_number(*x))
+        && values.iter().all(|x| matches!(x, Entry::Value(Value::Space(_)) | Entry::Value(Value::Whitespace(_)))) {
+
+        values.push(parse_decimal(input, options.chars));
+        return Ok(());
+    }
+    if let Some(keyword) = parse_keyword(input, options) {
+        values.push(keyword);
+        return Ok(());
+    }
+    match input.peek() {
+        Some(x) if options.chars.is_space(*x) => match options.spacing {
+            Spacing::Drop => { input.next(); },
+            Spacing::Chars => { let x = input.next().unwrap(); values.push(Entry::Value(Value::Space(x))); },
+            Spacing::Runs | Spacing::Trivia => { values.push(parse_spaces(input, options)); },
+        },
+        Some(x) if options.chars.is_number(*x) => match options.numbers {
+            Numbers::Off => { values.push(parse_symbol(input, options.chars)); },
+            Numbers::Digits => { values.push(parse_number(input, options.chars)); },
+            Numbers::Decimal => { values.push(parse_decimal(input, options.chars)); },
+        },
+        Some(x) if options.chars.is_symbol_start(*x) => { values.push(parse_symbol(input, options.chars)); },
+        Some(x) if options.strings.as_ref().map_or(false, |s| s.opens(*x)) => {
+            values.push(parse_quoted(input, options)?);
+        },
+        Some(x) if options.brackets.iter().any(|(open, _)| open == x) => {
+            match options.limits.depth {
+                Some(max) if depth >= max => {
+                    return Err(format!("Limit exceeded: maximum nesting depth of {} at {}", max, input.location()));
+                },
+                _ => { values.push(parse_group(input, options, depth + 1)?); },
+            }
+        },
+        Some(x) if options.brackets.iter().any(|(_, close)| close == x) => {
+            let x = *x;
+            return Err(format!("Unbalanced '{}' at {}", x, input.location()));
+        },
+        Some(_) => match parse_operator(input, options) {
+            Some(operator) => { values.push(operator); },
+            None => { let x = input.next().unwrap(); values.push(Entry::Value(Value::Punct(x))); },
+        },
+        None => { },
+    }
+    Ok(())
+}
+
+// Reads the field as text, only quoted strings and runs of spacing are kept
+// apart until the field is finished so that trimming leaves quoted spacing alone.
+fn parse_raw(input : &mut Input<impl Iterator<Item = char>>, options : &Options, values : &mut Vec<Entry>) -> Result<(), String> {
+    let is_quote = |x : char| options.strings.as_ref().map_or(false, |s| s.opens(x));
+    let is_separator = |x : char| options.key_value.as_ref().map_or(false, |kv| kv.separators.contains(&x));
+
+    match input.peek() {
+        Some(x) if is_quote(*x) => { values.push(parse_quoted(input, options)?); },
+        Some(x) if options.chars.is_space(*x) => { values.push(parse_spaces(input, options)); },
+        Some(_) => {
+            let x = input.next().unwrap();
+            let rest = take_while(input, |x| !options.chars.is_space(x) && !is_quote(x) && !is_separator(x) && !options.is_divider(x) && !(options.record.table && x == '\\'));
+            values.push(Entry::Value(Value::Symbol(format!("{}{}", x, rest))));
+        },
+        None => { },
+    }
+    Ok(())
+}
+
+// A run of spacing up to the next divider, leaving the CR of a CRLF line ending.
+// The first char is always taken, even a divider, so that callers make progress.
+fn parse_spaces(input : &mut Input<impl Iterator<Item = char>>, options : &Options) -> Entry {
+    let mut ret = String::new();
+    ret.extend(input.next());
+    while let Some(x) = input.peek() {
+        let x = *x;
+        if !options.chars.is_space(x) || options.is_divider(x) || (!ret.is_empty() && x == '\r' && options.record.crlf_endlines && options.endline == '\n' && input.peek_at(1) == Some(&'\n')) {
+            break;
+        }
+        ret.push(x);
+        input.next();
+    }
+    Entry::Value(Value::Whitespace(ret))
+}
+
+// An escape char which is also a quote char escapes by doubling, ie "a""b"
+fn parse_quoted(input : &mut Input<impl Iterator<Item = char>>, options : &Options) -> Result<Entry, String> {
+    let start = input.location();
+    let strings = options.strings.as_ref().unwrap();
+    let max = options.limits.string_length;
+
+    // A string opened by a pair only ends at its own close char
+    let close = input.peek().and_then(|x| strings.pairs.iter().find(|(open, _)| open == x)).map(|(_, close)| *close);
+    let is_end = |x : char| match close {
+        Some(close) => x == close,
+        None => strings.quote_chars.contains(&x),
+    };
+
+    let mut ret = String::new();
+    loop {
+        let string = match strings {
+            QuoteOpt { escape_char: None, .. } => parse_string(input, |_| false, |x| is_end(x), max),
+            QuoteOpt { escape_char: Some(escape_char), .. } => parse_string(input, |x| x == *escape_char, |x| is_end(x), max),
+        };
+        match string.map_err(|e| format!("{} at {}", e, start))? {
+            Entry::Value(Value::String(x)) => { ret.push_str(&x); },
+            _ => unreachable!(),
+        }
+        match max {
+            Some(max) if ret.chars().count() > max => { return Err(format!("Limit exceeded: maximum string length of {} at {}", max, start)); },
+            _ => { },
+        }
+        match strings.escape_char {
+            Some(e) if close.is_none() && strings.quote_chars.contains(&e) && input.peek() == Some(&e) => { ret.push(e); },
+            _ => { break; },
+        }
+    }
+
+    Ok(Entry::Value(Value::String(ret)))
+}
+
+fn parse_temporal(input : &mut Input<impl Iterator<Item = char>>, options : &Options) -> Option<Entry> {
+    const MAX_TEMPORAL_LENGTH : usize = 48;
+
+    match input.peek() {
+        Some(x) if x.is_ascii_digit() || *x == 'P' => { },
+        _ => { return None; },
+    }
+
+    let mut cs = vec![];
+    while let Some(c) = input.peek_at(cs.len()) {
+        if cs.len() == MAX_TEMPORAL_LENGTH || options.is_divider(*c) {
+            break;
+        }
+        cs.push(*c);
+    }
+
+    let (length, temporal) = temporal::recognize(&cs)?;
+
+    match input.peek_at(length) {
+        Some(x) if options.chars.is_symbol(*x) => None,
+        _ => {
+            for _ in 0..length {
+                input.next();
+            }
+            Some(Entry::Value(Value::Temporal(temporal)))
+        },
+    }
+}
+
+fn matches_ahead(input : &mut Input<impl Iterator<Item = char>>, text : &str, case_sensitive : bool) -> bool {
+    text.len() != 0 && text.chars().enumerate().all(|(i, c)| match input.peek_at(i) {
+        Some(x) if case_sensitive => *x == c,
+        Some(x) => x.to_lowercase().eq(c.to_lowercase()),
+        None => false,
+    })
+}
+
+fn parse_keyword(input : &mut Input<impl Iterator<Item = char>>, options : &Options) -> Option<Entry> {
+    let keywords = &options.keywords;
+    let (keyword, value) = keywords.trues.iter().map(|x| (x, Value::Bool(true)))
+        .chain(keywords.falses.iter().map(|x| (x, Value::Bool(false))))
+        .chain(keywords.nulls.iter().map(|x| (x, Value::Null)))
+        .chain(keywords.others.iter().map(|x| (x, Value::Keyword(x.clone()))))
+        .filter(|(x, _)| matches_ahead(input, x, keywords.case_sensitive))
+        .max_by_key(|(x, _)| x.chars().count())?;
+
+    let length = keyword.chars().count();
+    // The longest match wins across keywords and operators, ie "->" over a "-" keyword
+    let operator = options.operators.iter()
+        .filter(|op| matches_ahead(input, op, true))
+        .map(|op| op.chars().count())
+        .max()
+        .unwrap_or(0);
+    match input.peek_at(length) {
+        _ if operator > length => None,
+        Some(x) if options.chars.is_symbol(*x) => None,
+        _ => {
+            for _ in 0..length {
+                input.next();
+            }
+            Some(Entry::Value(value))
+        },
+    }
+}
+
+fn parse_operator(input : &mut Input<impl Iterator<Item = char>>, options : &Options) -> Option<Entry> {
+    let operator = options.operators.iter()
+        .filter(|op| matches_ahead(input, op, true))
+        .max_by_key(|op| op.chars().count())?;
+
+    for _ in operator.chars() {
+        input.next();
+    }
+
+    Some(Entry::Value(Value::Operator(operator.clone())))
+}
+
+fn parse_group(input : &mut Input<impl Iterator<Item = char>>, options : &Options, depth : usize) -> Result<Entry, String> {
+    let start = input.location();
+    let open = input.next().unwrap();
+    let close = options.brackets.iter().find(|(o, _)| *o == open).unwrap().1;
+
+    let mut items = vec![];
+    let mut values = vec![];
+
+    loop {
+        match input.peek() {
+            Some(x) if *x == close => {
+                if values.len() != 0 || items.len() != 0 {
+                    items.push(Entry::List(attach_trivia(values, options)));
+                }
+                input.next();
+                return Ok(group(open, items, close));
+            },
+            // The divider is kept between the items so that the group keeps its text
+            Some(x) if options.record.field_div.contains(&x) => {
+                let vs = std::mem::replace(&mut values, vec![]);
+                items.push(Entry::List(attach_trivia(vs, options)));
+                items.push(Entry::Value(Value::Punct(input.next().unwrap())));
+            },
+            Some(x) if options.brackets.iter().any(|(_, close)| close == x) => {
+                let x = *x;
+                return Err(format!("Mismatched '{}' at {} for '{}' at {}", x, input.location(), open, start));
+            },
+            // Endlines and sub field dividers do not divide anything inside of a group
+            Some(x) if options.is_divider(*x) => {
+                let x = input.next().unwrap();
+                match options.spacing {
+                    _ if !options.chars.is_space(x) => { values.push(Entry::Value(Value::Punct(x))); },
+                    Spacing::Drop => { },
+                    Spacing::Chars => { values.push(Entry::Value(Value::Space(x))); },
+                    Spacing::Runs | Spacing::Trivia => { values.push(Entry::Value(Value::Whitespace(x.to_string()))); },
+                }
+            },
+            Some(_) => { 
+                let at = input.location();
+                parse_value(input, options, &mut values, depth)?;
+                check_limit(options.limits.values_per_field, values.len(), "values per field", at)?;
+            },
+            None => { return Err(format!("Unbalanced '{}' at {} encountered end of input", open, start)); },
+        }
+    }
+}
+
+fn take_while(input : &mut Input<impl Iterator<Item = char>>, mut p : impl FnMut(char) -> bool) -> String {
+    let mut cs = vec![];
+
+    while let Some(c) = input.peek() {
+        if p(*c) {
+            cs.push(input.next().unwrap());
+        }
+        else {
+            break;
+        }
+    }
+
+    cs.into_iter().collect()
+}
+
+fn parse_number(input : &mut Input<impl Iterator<Item = char>>, chars : CharClass) -> Entry {
+    Entry::Value(Value::Number(take_while(input, |x| chars.is_number(x))))
+}
+
+fn parse_decimal(input : &mut Input<impl Iterator<Item = char>>, chars : CharClass) -> Entry {
+    let mut ret = String::new();
+    if matches!(input.peek(), Some('+') | Some('-')) {
+        ret.push(input.next().unwrap());
+    }
+    ret.push_str(&take_while(input, |x| chars.is_number(x)));
+    if input.peek() == Some(&'.') && input.peek_at(1).map_or(false, |x| chars.is_number(*x)) {
+        ret.push(input.next().unwrap());
+        ret.push_str(&take_while(input, |x| chars.is_number(x)));
+    }
+    if matches!(input.peek(), Some('e') | Some('E')) {
+        let digit = if matches!(input.peek_at(1), Some('+') | Some('-')) { 2 } else { 1 };
+        if input.peek_at(digit).map_or(false, |x| chars.is_number(*x)) {
+            for _ in 0..digit {
+                ret.push(input.next().unwrap());
+            }
+            ret.push_str(&take_while(input, |x| chars.is_number(x)));
+        }
+    }
+    Entry::Value(Value::Number(ret))
+}
+
+fn parse_symbol(input : &mut Input<impl Iterator<Item = char>>, chars : CharClass) -> Entry {
+    Entry::Value(Value::Symbol(take_while(input, |x| chars.is_symbol(x))))
+}
+
+fn parse_string( input : &mut impl Iterator<Item = char> 
+               , mut is_escape : impl FnMut(char) -> bool
+               , mut is_end : impl FnMut(char) -> bool
+               , max_length : Option<usize>) 
+               -> Result<Entry, String> {
+
+    input.next(); // Get rid of initial quote
+   
+    let mut ret = vec![];
+    let mut escape = None;
+    loop {
+        match input.next() {
+            None => { return Err("String encountered end of input".into()); },
+            Some(x) if escape.is_some() && is_end(x) => { ret.push(x); escape = None; },
+            Some(x) if escape.is_some() && is_escape(x) => { ret.push(x); escape = None; },
+            Some(x) if escape.is_some() => { ret.push(escape.unwrap()); ret.push(x); escape = None; },
+            Some(x) if is_end(x) => { break; },
+            Some(x) if is_escape(x) => { escape = Some(x); },
+            Some(x) => { ret.push(x); },
+        }
+        match max_length {
+            Some(max) if ret.len() > max => { return Err(format!("Limit exceeded: maximum string length of {}", max)); },
+            _ => { },
+        }
+    }
+
+    Ok(Entry::Value(Value::String(ret.into_iter().collect())))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use structuralize::pattern::check::*;
+    use structuralize::pattern::data::*;
+    use structuralize::pattern::matcher::*;
+
+    fn num(input : u32) -> Value {
+        Value::Number(format!("{}", input))
+    }
+
+    fn precord(p : Pattern<Value>) -> Pattern<Value> {
+        Pattern::Cons { name: "Record".into(), params: vec![p] }
+    }
+
+    fn pfield(p : Pattern<Value>) -> Pattern<Value> {
+        Pattern::Cons { name: "Field".into(), params: vec![p] }
+    }
+
+    fn pexact_list(ps : Vec<Pattern<Value>>) -> Pattern<Value> {
+        Pattern::ExactList(ps)
+    }
+
+    fn plist_path(ps : Vec<Pattern<Value>>) -> Pattern<Value> {
+        Pattern::ListPath(ps)
+    }
+
+    fn m<'a>(p : Pattern<Value>, d : &'a Entry) -> Vec<HashMap<Box<str>, &'a Entry>> {
+        let tc = check_pattern(p).unwrap();
+        pattern_match(&tc, d).map(|x| x.into_iter().collect::<HashMap<_, _>>()).collect::<Vec<_>>()
+    }
+
+    fn p_empty_record() -> Pattern<Value> {
+            plist_path(vec![precord(pexact_list(vec![]))])
+    }
+
+    #[test]
+    fn parse_records_should_parse_multi_line_records_with_space() {
+        let mut input = "1 2\n3 4\n\n\n5 6\n7 8".chars();
+        let output = parse_records(&mut input, &Options::default().multi_line_records().field_dividers(&['\n']).preserve_spacing(true)).unwrap();
+
+        let filled_pattern = plist_path(
+            vec![precord(
+                pexact_list(vec![Pattern::CaptureVar("line_one".into()), Pattern::CaptureVar("line_two".into())]))]);
+        let filled_records = m(filled_pattern, &output);
+
+        assert_eq!(filled_records.len(), 2);
+
+        let filled_internal_pattern = pfield(pexact_list(vec![ Pattern::CaptureVar("a".into())
+                                                             , Pattern::CaptureVar("b".into())
+                                                             , Pattern::CaptureVar("c".into())
+                                                             ]));
+
+        // Filled record 1
+        let filled = filled_records[0].get("line_one").unwrap();
+
+        let filled_results = m(filled_internal_pattern.clone(), filled);
+
+        assert_eq!(filled_results.len(), 1);
+        assert_eq!(filled_results[0].get("a").unwrap(), &&Entry::Value(num(1)));
+        assert_eq!(filled_results[0].get("b").unwrap(), &&Entry::Value(Value::Space(' ')));
+        assert_eq!(filled_results[0].get("c").unwrap(), &&Entry::Value(num(2)));
+
+        let filled = filled_records[0].get("line_two").unwrap();
+
+        let filled_results = m(filled_internal_pattern.clone(), filled);
+
+        assert_eq!(filled_results.len(), 1);
+        assert_eq!(filled_results[0].get("a").unwrap(), &&Entry::Value(num(3)));
+        assert_eq!(filled_results[0].get("b").unwrap(), &&Entry::Value(Value::Space(' ')));
+        assert_eq!(filled_results[0].get("c").unwrap(), &&Entry::Value(num(4)));
 
         // Filled record 2
         let filled = filled_records[1].get("line_one").unwrap();
@@ -268,7 +1180,8 @@ mod test {
 
         assert_eq!(filled_results.len(), 1);
         assert_eq!(filled_results[0].get("a").unwrap(), &&Entry::Value(num(5)));
-        assert_eq!(filled_results[0].get("b").unwrap(), &&Entry::Value(num(6)));
+        assert_eq!(filled_results[0].get("b").unwrap(), &&Entry::Value(Value::Space(' ')));
+        assert_eq!(filled_results[0].get("c").unwrap(), &&Entry::Value(num(6)));
 
         let filled = filled_records[1].get("line_two").unwrap();
 
@@ -276,7 +1189,8 @@ mod test {
 
         assert_eq!(filled_results.len(), 1);
         assert_eq!(filled_results[0].get("a").unwrap(), &&Entry::Value(num(7)));
-        assert_eq!(filled_results[0].get("b").unwrap(), &&Entry::Value(num(8)));
+        assert_eq!(filled_results[0].get("b").unwrap(), &&Entry::Value(Value::Space(' ')));
+        assert_eq!(filled_results[0].get("c").unwrap(), &&Entry::Value(num(8)));
 
         // Empty Record
         let empty_records = m(p_empty_record(), &output);
@@ -284,8 +1198,8 @@ mod test {
     }
 
     #[test]
-    fn parse_records_should_parse_multi_line_records_without_final_blank_line() {
-        let mut input = "1 2\n3 4\n\n5 6\n7 8".chars();
+    fn parse_records_should_parse_multi_line_records_with_multi_blank_lines() {
+        let mut input = "1 2\n3 4\n\n\n5 6\n7 8".chars();
         let output = parse_records(&mut input, &Options::default().multi_line_records().field_dividers(&['\n'])).unwrap();
 
         let filled_pattern = plist_path(
@@ -335,12 +1249,12 @@ mod test {
 
         // Empty Record
         let empty_records = m(p_empty_record(), &output);
-        assert_eq!(empty_records.len(), 0);
+        assert_eq!(empty_records.len(), 1);
     }
 
     #[test]
-    fn parse_records_should_parse_multi_line_records() {
-        let mut input = "1 2\n3 4\n\n5 6\n7 8\n\n".chars();
+    fn parse_records_should_parse_multi_line_records_without_final_blank_line() {
+        let mut input = "1 2\n3 4\n\n5 6\n7 8".chars();
         let output = parse_records(&mut input, &Options::default().multi_line_records().field_dividers(&['\n'])).unwrap();
 
         let filled_pattern = plist_path(
@@ -394,15 +1308,70 @@ mod test {
     }
 
     #[test]
-    fn parse_records_should_parse_single_line_records() {
-        let mut input = "1,2,3\n4,5,6".chars();
-        let output = parse_records(&mut input, &Options::default().single_line_records()).unwrap();
+    fn parse_records_should_parse_multi_line_records() {
+        let mut input = "1 2\n3 4\n\n5 6\n7 8\n\n".chars();
+        let output = parse_records(&mut input, &Options::default().multi_line_records().field_dividers(&['\n'])).unwrap();
 
-        let record_pattern = plist_path(vec![
-                                precord(pexact_list(vec![ pfield(pexact_list(vec![Pattern::CaptureVar("a".into())]))
-                                                        , pfield(pexact_list(vec![Pattern::CaptureVar("b".into())]))
-                                                        , pfield(pexact_list(vec![Pattern::CaptureVar("c".into())]))
-                                                        ]))]);
+        let filled_pattern = plist_path(
+            vec![precord(
+                pexact_list(vec![Pattern::CaptureVar("line_one".into()), Pattern::CaptureVar("line_two".into())]))]);
+        let filled_records = m(filled_pattern, &output);
+
+        assert_eq!(filled_records.len(), 2);
+
+        let filled_internal_pattern = pfield(pexact_list(vec![ Pattern::CaptureVar("a".into())
+                                                             , Pattern::CaptureVar("b".into())
+                                                             ]));
+
+        // Filled record 1
+        let filled = filled_records[0].get("line_one").unwrap();
+
+        let filled_results = m(filled_internal_pattern.clone(), filled);
+
+        assert_eq!(filled_results.len(), 1);
+        assert_eq!(filled_results[0].get("a").unwrap(), &&Entry::Value(num(1)));
+        assert_eq!(filled_results[0].get("b").unwrap(), &&Entry::Value(num(2)));
+
+        let filled = filled_records[0].get("line_two").unwrap();
+
+        let filled_results = m(filled_internal_pattern.clone(), filled);
+
+        assert_eq!(filled_results.len(), 1);
+        assert_eq!(filled_results[0].get("a").unwrap(), &&Entry::Value(num(3)));
+        assert_eq!(filled_results[0].get("b").unwrap(), &&Entry::Value(num(4)));
+
+        // Filled record 2
+        let filled = filled_records[1].get("line_one").unwrap();
+
+        let filled_results = m(filled_internal_pattern.clone(), filled);
+
+        assert_eq!(filled_results.len(), 1);
+        assert_eq!(filled_results[0].get("a").unwrap(), &&Entry::Value(num(5)));
+        assert_eq!(filled_results[0].get("b").unwrap(), &&Entry::Value(num(6)));
+
+        let filled = filled_records[1].get("line_two").unwrap();
+
+        let filled_results = m(filled_internal_pattern, filled);
+
+        assert_eq!(filled_results.len(), 1);
+        assert_eq!(filled_results[0].get("a").unwrap(), &&Entry::Value(num(7)));
+        assert_eq!(filled_results[0].get("b").unwrap(), &&Entry::Value(num(8)));
+
+        // Empty Record
+        let empty_records = m(p_empty_record(), &output);
+        assert_eq!(empty_records.len(), 0);
+    }
+
+    #[test]
+    fn parse_records_should_parse_single_line_records() {
+        let mut input = "1,2,3\n4,5,6".chars();
+        let output = parse_records(&mut input, &Options::default().single_line_records()).unwrap();
+
+        let record_pattern = plist_path(vec![
+                                precord(pexact_list(vec![ pfield(pexact_list(vec![Pattern::CaptureVar("a".into())]))
+                                                        , pfield(pexact_list(vec![Pattern::CaptureVar("b".into())]))
+                                                        , pfield(pexact_list(vec![Pattern::CaptureVar("c".into())]))
+                                                        ]))]);
 
         let records = m(record_pattern, &output);
 
@@ -449,24 +1418,1073 @@ mod test {
         assert_eq!(empty_records.len(), 1);
     }
 
+    fn sym(input : &str) -> Entry {
+        Entry::Value(Value::Symbol(input.into()))
+    }
+
+    fn punct(input : char) -> Entry {
+        Entry::Value(Value::Punct(input))
+    }
+
+    #[test]
+    fn parse_records_should_parse_nested_groups() {
+        let mut input = "tags=[a, b, [c, d]],f(x, y)".chars();
+        let output = parse_records(&mut input, &Options::default().brackets(&[('(', ')'), ('[', ']')])).unwrap();
+
+        let inner = group('[', vec![Entry::List(vec![sym("c")]), punct(','), Entry::List(vec![sym("d")])], ']');
+        let tags = group('[', vec![Entry::List(vec![sym("a")]), punct(','), Entry::List(vec![sym("b")]), punct(','), Entry::List(vec![inner])], ']');
+        let call = group('(', vec![Entry::List(vec![sym("x")]), punct(','), Entry::List(vec![sym("y")])], ')');
+
+        let expected = Entry::List(vec![record(vec![ field(vec![sym("tags"), punct('='), tags])
+                                                   , field(vec![sym("f"), call])
+                                                   ])]);
+
+        assert_eq!(output, expected);
+        assert_eq!(output.from_list().unwrap()[0].from_record().unwrap()[0].from_list().unwrap()[1].text(), "f(x,y)");
+    }
+
+    #[test]
+    fn parse_records_should_parse_empty_groups() {
+        let mut input = "(),(,)".chars();
+        let output = parse_records(&mut input, &Options::default().brackets(&[('(', ')')])).unwrap();
+
+        let expected = Entry::List(vec![record(vec![ field(vec![group('(', vec![], ')')])
+                                                   , field(vec![group('(', vec![Entry::List(vec![]), punct(','), Entry::List(vec![])], ')')])
+                                                   ])]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_records_should_report_unbalanced_groups() {
+        let options = Options::default().brackets(&[('(', ')'), ('[', ']')]);
+
+        let output = parse_records(&mut "1,2\nf(x, y".chars(), &options);
+        assert_eq!(output, Err("Unbalanced '(' at line 2, column 2 encountered end of input".into()));
+
+        let output = parse_records(&mut "a)".chars(), &options);
+        assert_eq!(output, Err("Unbalanced ')' at line 1, column 2".into()));
+
+        let output = parse_records(&mut "[a)".chars(), &options);
+        assert_eq!(output, Err("Mismatched ')' at line 1, column 3 for '[' at line 1, column 1".into()));
+    }
+
+    fn op(input : &str) -> Entry {
+        Entry::Value(Value::Operator(input.into()))
+    }
+
+    #[test]
+    fn parse_records_should_parse_longest_operator() {
+        let mut input = "a->b==c<=d::e...f..g".chars();
+        let output = parse_records(&mut input, &Options::default().operators(&["->", "=", "==", "<=", "::", "..."])).unwrap();
+
+        let expected = Entry::List(vec![record(vec![field(vec![ sym("a"), op("->"), sym("b"), op("=="), sym("c"), op("<=")
+                                                              , sym("d"), op("::"), sym("e"), op("..."), sym("f")
+                                                              , punct('.'), punct('.'), sym("g")
+                                                              ])])]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_records_should_classify_standard_chars() {
+        let mut input = "½ Ⅻ\u{a0}x²".chars();
+        let output = parse_records(&mut input, &Options::default().standard_chars()).unwrap();
+
+        let expected = Entry::List(vec![record(vec![field(vec![ Entry::Value(Value::Number("½".into()))
+                                                              , Entry::Value(Value::Number("Ⅻ".into()))
+                                                              , sym("x²")
+                                                              ])])]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_records_should_classify_ascii_chars() {
+        let mut input = "½ Ⅻ\u{a0}x²_1 é".chars();
+        let output = parse_records(&mut input, &Options::default().ascii_chars()).unwrap();
+
+        let expected = Entry::List(vec![record(vec![field(vec![ punct('½'), punct('Ⅻ'), punct('\u{a0}')
+                                                              , sym("x"), punct('²'), sym("_1"), punct('é')
+                                                              ])])]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_records_should_classify_unicode_chars() {
+        let mut input = "½ Ⅻ\u{a0}x²ñ \u{663}\u{664} e\u{301}".chars();
+        let output = parse_records(&mut input, &Options::default().unicode_chars()).unwrap();
+
+        let expected = Entry::List(vec![record(vec![field(vec![ punct('½'), sym("Ⅻ"), punct('\u{a0}'), sym("x"), punct('²'), sym("ñ")
+                                                              , Entry::Value(Value::Number("\u{663}\u{664}".into()))
+                                                              , sym("e\u{301}")
+                                                              ])])]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_records_should_recognize_temporals() {
+        let mut input = "2026-10-18T07:27:00Z,10:15:30,PT5M,Pending,2026-10-18x,2026-13-01".chars();
+        let output = parse_records(&mut input, &Options::default().recognize_temporals(true)).unwrap();
+
+        let records = output.from_list().unwrap();
+        let fields = records[0].from_record().unwrap()[0].from_list().unwrap();
+
+        let texts = fields.iter().map(|f| f.from_field().unwrap()[0].from_list().unwrap()
+                                           .iter()
+                                           .map(|v| match v.from_value().unwrap() {
+                                                Value::Temporal(t) => format!("T({})", t.text),
+                                                x => format!("{:?}", x),
+                                           })
+                                           .collect::<Vec<_>>())
+                              .collect::<Vec<_>>();
+
+        assert_eq!(texts[0], vec!["T(2026-10-18T07:27:00Z)"]);
+        assert_eq!(texts[1], vec!["T(10:15:30)"]);
+        assert_eq!(texts[2], vec!["T(PT5M)"]);
+        assert_eq!(texts[3], vec!["Symbol(\"Pending\")"]);
+        assert_eq!(texts[4], vec!["Number(\"2026\")", "Punct('-')", "Number(\"10\")", "Punct('-')", "Number(\"18\")", "Symbol(\"x\")"]);
+        assert_eq!(texts[5], vec!["Number(\"2026\")", "Punct('-')", "Number(\"13\")", "Punct('-')", "Number(\"01\")"]);
+    }
+
+    #[test]
+    fn parse_records_should_prefer_longer_operators_over_keywords() {
+        let options = Options::default().null_keywords(&["-"]).operators(&["->"]);
+        let output = parse_records(&mut "a->b,-".chars(), &options).unwrap();
+
+        let expected = Entry::List(vec![record(vec![ field(vec![sym("a"), op("->"), sym("b")])
+                                                   , field(vec![Entry::Value(Value::Null)])
+                                                   ])]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_records_should_parse_keywords() {
+        let mut input = "true,FALSE,NA,-,-5,trueish,Pending,null\n".chars();
+        let options = Options::default().bool_keywords(&["true"], &["false"])
+                                        .null_keywords(&["null", "NA", "-"])
+                                        .keywords(&["pending"])
+                                        .case_sensitive_keywords(false);
+        let output = parse_records(&mut input, &options).unwrap();
+
+        let expected = Entry::List(vec![record(vec![ field(vec![Entry::Value(Value::Bool(true))])
+                                                   , field(vec![Entry::Value(Value::Bool(false))])
+                                                   , field(vec![Entry::Value(Value::Null)])
+                                                   , field(vec![Entry::Value(Value::Null)])
+                                                   , field(vec![punct('-'), Entry::Value(num(5))])
+                                                   , field(vec![sym("trueish")])
+                                                   , field(vec![Entry::Value(Value::Keyword("pending".into()))])
+                                                   , field(vec![Entry::Value(Value::Null)])
+                                                   ])]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_records_should_parse_case_sensitive_keywords() {
+        let mut input = "true,True".chars();
+        let output = parse_records(&mut input, &Options::default().bool_keywords(&["true"], &["false"])).unwrap();
+
+        let expected = Entry::List(vec![record(vec![ field(vec![Entry::Value(Value::Bool(true))])
+                                                   , field(vec![sym("True")])
+                                                   ])]);
+
+        assert_eq!(output, expected);
+    }
+
+    fn ws(input : &str) -> Entry {
+        Entry::Value(Value::Whitespace(input.into()))
+    }
+
+    #[test]
+    fn parse_records_should_collapse_spacing_runs() {
+        let mut input = "  a    b\t,c ".chars();
+        let output = parse_records(&mut input, &Options::default().preserve_spacing_runs()).unwrap();
+
+        let expected = Entry::List(vec![record(vec![ field(vec![ws("  "), sym("a"), ws("    "), sym("b"), ws("\t")])
+                                                   , field(vec![sym("c"), ws(" ")])
+                                                   ])]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_records_should_attach_spacing_as_trivia() {
+        let mut input = "  a    b\t,c,  \n".chars();
+        let output = parse_records(&mut input, &Options::default().preserve_spacing_as_trivia()).unwrap();
+
+        let trivia = |l : &str, e : Entry, t : &str| Entry::Trivia(vec![ws(l), e, ws(t)]);
+
+        let expected = Entry::List(vec![record(vec![ field(vec![trivia("  ", sym("a"), ""), trivia("    ", sym("b"), "\t")])
+                                                   , field(vec![trivia("", sym("c"), "")])
+                                                   , field(vec![ws("  ")])
+                                                   ])]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_document_should_look_up_fields_by_header_name() {
+        let mut input = "name,age\nalice,30\nbob,41".chars();
+        let output = parse_document(&mut input, &Options::default().has_header(true)).unwrap();
+
+        assert_eq!(output.header, Some(vec!["name".to_string(), "age".to_string()]));
+        assert_eq!(output.records.len(), 2);
+
+        let rows = output.rows().collect::<Vec<_>>();
+        assert_eq!(rows[0].get("name").unwrap(), &field(vec![sym("alice")]));
+        assert_eq!(rows[1].get("age").unwrap(), &field(vec![Entry::Value(num(41))]));
+        assert_eq!(rows[1].get("height"), Err("No column named 'height'".into()));
+    }
+
+    #[test]
+    fn parse_document_should_keep_header_names_as_written() {
+        let mut input = "First Name,Age, (years)\nAda Lovelace,36,x".chars();
+        let output = parse_document(&mut input, &Options::default().has_header(true).brackets(&[('(', ')')])).unwrap();
+
+        assert_eq!(output.header, Some(vec!["First Name".to_string(), "Age".to_string(), "(years)".to_string()]));
+        let rows = output.rows().collect::<Vec<_>>();
+        assert_eq!(rows[0].get("First Name").unwrap(), &field(vec![sym("Ada"), sym("Lovelace")]));
+    }
+
+    #[test]
+    fn parse_document_should_keep_first_record_without_header() {
+        let mut input = "name,age\nalice,30".chars();
+        let output = parse_document(&mut input, &Options::default()).unwrap();
+
+        assert_eq!(output.header, None);
+        assert_eq!(output.records.len(), 2);
+    }
+
+    #[test]
+    fn parse_document_should_report_header_errors() {
+        let options = Options::default().has_header(true);
+
+        let output = parse_document(&mut "".chars(), &options);
+        assert_eq!(output, Err("Missing header record".into()));
+
+        let output = parse_document(&mut "name,,age\n1,2,3".chars(), &options);
+        assert_eq!(output, Err("Missing header name for column 2".into()));
+
+        let output = parse_document(&mut "name,age,name\n1,2,3".chars(), &options);
+        assert_eq!(output, Err("Duplicate header name 'name' for columns 1 and 3".into()));
+
+        let output = parse_document(&mut "name,age\n1,2\n3".chars(), &options);
+        assert_eq!(output, Err("Record 2 has 1 fields but the header has 2".into()));
+    }
+
+    fn string(input : &str) -> Entry {
+        Entry::Value(Value::String(input.into()))
+    }
+
+    #[test]
+    fn parse_records_should_parse_logfmt_key_values() {
+        let mut input = "level=info msg=\"a b=c\" debug at=10:15".chars();
+        let options = Options::default().field_dividers(&[' ']).key_values(&['=']);
+        let output = parse_records(&mut input, &options).unwrap();
+
+        let expected = Entry::List(vec![record(vec![ key_value_field(vec![sym("level")], vec![sym("info")])
+                                                   , key_value_field(vec![sym("msg")], vec![string("a b=c")])
+                                                   , key_value_field(vec![sym("debug")], vec![])
+                                                   , key_value_field(vec![sym("at")], vec![Entry::Value(num(10)), punct(':'), Entry::Value(num(15))])
+                                                   ])]);
+
+        assert_eq!(output, expected);
+
+        let records = output.from_list().unwrap();
+        assert_eq!(records[0].lookup("msg").unwrap(), vec![&Entry::List(vec![string("a b=c")])]);
+        assert_eq!(records[0].lookup("missing").unwrap().len(), 0);
+    }
+
+    #[test]
+    fn parse_records_should_split_on_first_key_value_separator() {
+        let mut input = "Host: example.com:80\nAccept: */*".chars();
+        let options = Options::default().field_dividers(&[]).key_values(&[':']);
+        let output = parse_records(&mut input, &options).unwrap();
+
+        let records = output.from_list().unwrap();
+        assert_eq!(records[0].lookup("Host").unwrap()[0].text(), "example.com:80");
+        assert_eq!(records[1].lookup("Accept").unwrap()[0].text(), "*/*");
+    }
+
+    #[test]
+    fn parse_records_should_apply_duplicate_key_policy() {
+        let input = "a=1,b=2,a=3";
+        let options = || Options::default().key_values(&['=']);
+
+        let output = parse_records(&mut input.chars(), &options()).unwrap();
+        let values = output.from_list().unwrap()[0].lookup("a").unwrap().iter().map(|x| x.text()).collect::<Vec<_>>();
+        assert_eq!(values, vec!["1", "3"]);
+
+        let output = parse_records(&mut input.chars(), &options().keep_first_duplicate_keys()).unwrap();
+        let values = output.from_list().unwrap()[0].lookup("a").unwrap().iter().map(|x| x.text()).collect::<Vec<_>>();
+        assert_eq!(values, vec!["1"]);
+
+        let output = parse_records(&mut input.chars(), &options().keep_last_duplicate_keys()).unwrap();
+        let values = output.from_list().unwrap()[0].lookup("a").unwrap().iter().map(|x| x.text()).collect::<Vec<_>>();
+        assert_eq!(values, vec!["3"]);
+
+        let output = parse_records(&mut input.chars(), &options().reject_duplicate_keys());
+        assert_eq!(output, Err("Duplicate key 'a' in record 1".into()));
+    }
+
+    #[test]
+    fn parse_records_should_parse_sub_fields() {
+        let mut input = "PID|1|doe^john&q|x".chars();
+        let options = Options::default().field_dividers(&['|']).sub_field_dividers(&[&['^'], &['&']]);
+        let output = parse_records(&mut input, &options).unwrap();
+
+        let leaf = |values : Vec<Entry>| Entry::List(vec![Entry::List(vec![Entry::List(values)])]);
+        let nested = |values : Vec<Entry>| Entry::Field(vec![leaf(values)]);
+
+        let name = Entry::Field(vec![Entry::List(vec![ Entry::List(vec![Entry::List(vec![sym("doe")])])
+                                                     , Entry::List(vec![Entry::List(vec![sym("john")]), Entry::List(vec![sym("q")])])
+                                                     ])]);
+
+        let expected = Entry::List(vec![record(vec![ nested(vec![sym("PID")])
+                                                   , nested(vec![Entry::Value(num(1))])
+                                                   , name
+                                                   , nested(vec![sym("x")])
+                                                   ])]);
+
+        assert_eq!(output, expected);
+
+        let cv = |name : &str| Pattern::CaptureVar(name.into());
+        let pattern = plist_path(vec![precord(pexact_list(vec![
+                        cv("a"), 
+                        cv("b"),
+                        pfield(pexact_list(vec![ pexact_list(vec![cv("family")])
+                                               , pexact_list(vec![pexact_list(vec![cv("given")]), cv("c")])
+                                               ])),
+                        cv("d"),
+                      ]))]);
+
+        let results = m(pattern, &output);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].get("given").unwrap(), &&sym("john"));
+    }
+
+    #[test]
+    fn parse_records_should_collapse_dividers() {
+        let mut input = "  PID TTY\t\t TIME\n   1 ?      0:01  \n".chars();
+        let options = Options::default().field_dividers(&[' ', '\t']).collapse_dividers(true);
+        let output = parse_records(&mut input, &options).unwrap();
+
+        let expected = Entry::List(vec![ record(vec![field(vec![sym("PID")]), field(vec![sym("TTY")]), field(vec![sym("TIME")])])
+                                       , record(vec![ field(vec![Entry::Value(num(1))])
+                                                    , field(vec![punct('?')])
+                                                    , field(vec![Entry::Value(num(0)), punct(':'), Entry::Value(Value::Number("01".into()))])
+                                                    ])
+                                       ]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_records_should_not_collapse_dividers_by_default() {
+        let mut input = "a  b".chars();
+        let output = parse_records(&mut input, &Options::default().field_dividers(&[' '])).unwrap();
+
+        let expected = Entry::List(vec![record(vec![field(vec![sym("a")]), field(vec![]), field(vec![sym("b")])])]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_records_should_keep_empty_records_by_default() {
+        let output = parse_records(&mut "1\n\n \n2\n  ".chars(), &Options::default()).unwrap();
+        assert_eq!(output.from_list().unwrap().len(), 5);
+
+        let output = parse_records(&mut "1\n\n \n2\n  \n".chars(), &Options::default()).unwrap();
+        assert_eq!(output.from_list().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn parse_records_should_drop_empty_records() {
+        let options = Options::default().drop_empty_records();
+        let output = parse_records(&mut "1\n\n \n2\n".chars(), &options).unwrap();
+
+        let expected = Entry::List(vec![ record(vec![field(vec![Entry::Value(num(1))])])
+                                       , record(vec![field(vec![Entry::Value(num(2))])])
+                                       ]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_records_should_drop_whitespace_only_records() {
+        let options = Options::default().preserve_spacing(true).drop_empty_records();
+        let output = parse_records(&mut "1\n \t\n2".chars(), &options).unwrap();
+        assert_eq!(output.from_list().unwrap().len(), 3);
+
+        let options = options.whitespace_lines_are_blank(true);
+        let output = parse_records(&mut "1\n \t\n2".chars(), &options).unwrap();
+        assert_eq!(output.from_list().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn parse_records_should_reject_empty_records() {
+        let options = Options::default().reject_empty_records();
+
+        let output = parse_records(&mut "1,2\n3,4\n".chars(), &options);
+        assert!(output.is_ok());
+
+        let output = parse_records(&mut "1,2\n\n3,4".chars(), &options);
+        assert_eq!(output, Err("Empty record at line 2".into()));
+    }
+
+    #[test]
+    fn parse_records_should_treat_whitespace_lines_as_blank_lines() {
+        let options = Options::default().multi_line_records().field_dividers(&['\n']).preserve_spacing(true);
+
+        let output = parse_records(&mut "1\n2\n \t\n3\n4".chars(), &options).unwrap();
+        assert_eq!(output.from_list().unwrap().len(), 1);
+
+        let options = options.whitespace_lines_are_blank(true);
+        let output = parse_records(&mut "1\n2\n \t\n3\n4".chars(), &options).unwrap();
+
+        let expected = Entry::List(vec![ record(vec![field(vec![Entry::Value(num(1))]), field(vec![Entry::Value(num(2))])])
+                                       , record(vec![field(vec![Entry::Value(num(3))]), field(vec![Entry::Value(num(4))])])
+                                       ]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_records_should_treat_crlf_lines_as_blank_lines() {
+        let options = Options::default().multi_line_records().field_dividers(&['\n']);
+        let output = parse_records(&mut "1\r\n2\r\n\r\n3\r\n4".chars(), &options).unwrap();
+
+        let expected = Entry::List(vec![ record(vec![field(vec![Entry::Value(num(1))]), field(vec![Entry::Value(num(2))])])
+                                       , record(vec![field(vec![Entry::Value(num(3))]), field(vec![Entry::Value(num(4))])])
+                                       ]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_records_should_not_split_on_whitespace_lines_with_whitespace_dividers() {
+        let options = Options::default().multi_line_records().field_dividers(&['\n', ' ']).whitespace_lines_are_blank(true);
+        let output = parse_records(&mut "1 2\n  \n3".chars(), &options).unwrap();
+
+        let expected = Entry::List(vec![ record(vec![field(vec![Entry::Value(num(1))]), field(vec![Entry::Value(num(2))])])
+                                       , record(vec![field(vec![Entry::Value(num(3))])])
+                                       ]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_records_should_report_unterminated_string_position() {
+        let output = parse_records(&mut "1,2\n3,'abc".chars(), &Options::default());
+        assert_eq!(output, Err("String encountered end of input at line 2, column 3".into()));
+    }
+
+    #[test]
+    fn parse_records_should_enforce_limits() {
+        let output = parse_records(&mut "1,'abcdef'".chars(), &Options::default().max_string_length(5));
+        assert_eq!(output, Err("Limit exceeded: maximum string length of 5 at line 1, column 3".into()));
+
+        let output = parse_records(&mut "1,'abcdef".chars(), &Options::default().max_string_length(5));
+        assert_eq!(output, Err("Limit exceeded: maximum string length of 5 at line 1, column 3".into()));
+
+        let output = parse_records(&mut "a b c d".chars(), &Options::default().max_values_per_field(3));
+        assert_eq!(output, Err("Limit exceeded: maximum of 3 values per field at line 1, column 7".into()));
+
+        let output = parse_records(&mut "1,2\n1,2,3,4".chars(), &Options::default().max_fields_per_record(3));
+        assert_eq!(output, Err("Limit exceeded: maximum of 3 fields per record at line 2, column 8".into()));
+
+        let output = parse_records(&mut "1\n2\n3".chars(), &Options::default().max_records(2));
+        assert_eq!(output, Err("Limit exceeded: maximum of 2 records at line 3, column 2".into()));
+
+        let options = Options::default().brackets(&[('(', ')')]).max_depth(2);
+        assert!(parse_records(&mut "((a))".chars(), &options).is_ok());
+        let output = parse_records(&mut "(((a)))".chars(), &options);
+        assert_eq!(output, Err("Limit exceeded: maximum nesting depth of 2 at line 1, column 3".into()));
+
+        let options = Options::default().max_input_size(5);
+        assert!(parse_records(&mut "1,2,3".chars(), &options).is_ok());
+        let output = parse_records(&mut "1,2,'3".chars(), &options);
+        assert_eq!(output, Err("Limit exceeded: maximum input size of 5 chars at line 1, column 6".into()));
+    }
+
+    #[test]
+    fn parse_records_should_parse_doubled_quote_escapes() {
+        let mut input = "\"a\"\"b\",\"\",\"\"\"\"".chars();
+        let output = parse_records(&mut input, &Options::default().allow_strings_with_escape(&['"'], '"')).unwrap();
+
+        let expected = Entry::List(vec![record(vec![ field(vec![string("a\"b")])
+                                                   , field(vec![string("")])
+                                                   , field(vec![string("\"")])
+                                                   ])]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn validate_should_accept_presets() {
+        for options in [Options::default(), Options::csv(), Options::tsv(), Options::colon_separated(), Options::logfmt(), Options::whitespace_columns(), Options::paragraphs()] {
+            assert_eq!(options.validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn validate_should_report_shadowed_roles() {
+        let options = Options::default().field_dividers(&['"', '\n']);
+        assert_eq!(options.validate(), Err(vec![ "'\\n' as field divider is shadowed by '\\n' as endline".to_string()
+                                              , "'\"' as quote char is shadowed by '\"' as field divider".to_string()
+                                              ]));
+
+        let options = Options::default().sub_field_dividers(&[&[';'], &[',']]).key_values(&[';']).allow_strings_with_escape(&['"'], '=');
+        assert_eq!(options.validate(), Err(vec![ "',' as sub field divider level 2 is shadowed by ',' as field divider".to_string()
+                                              , "';' as key value separator is shadowed by ';' as sub field divider level 1".to_string()
+                                              ]));
+
+        let options = Options::default().allow_strings_with_escape(&['\''], ',');
+        assert_eq!(options.validate(), Err(vec!["',' as escape char is also used as field divider".to_string()]));
+
+        let options = Options::default().brackets(&[('(', ')')]).operators(&[",,", "->", "'x", "and", "1+", "()", " =", ""]);
+        assert_eq!(options.validate(), Err(vec![ "\",,\" as operator is shadowed by ',' as field divider".to_string()
+                                              , "\"'x\" as operator is shadowed by '\\'' as quote char".to_string()
+                                              , "\"and\" as operator is shadowed by 'a' as symbol".to_string()
+                                              , "\"1+\" as operator is shadowed by '1' as number".to_string()
+                                              , "\"()\" as operator is shadowed by '(' as bracket".to_string()
+                                              , "\" =\" as operator is shadowed by ' ' as spacing".to_string()
+                                              , "\"\" as operator is empty".to_string()
+                                              ]));
+    }
+
+    #[test]
+    fn validate_should_allow_endline_field_dividers_in_multi_line_records() {
+        let options = Options::default().multi_line_records().field_dividers(&['\n']);
+        assert_eq!(options.validate(), Ok(()));
+
+        let options = Options::default().single_line_records().field_dividers(&['\n']);
+        assert!(options.validate().is_err());
+    }
+
+    #[test]
+    fn validated_should_join_reports() {
+        let output = Options::default().field_dividers(&['\'']).validated().unwrap_err();
+        assert_eq!(output, "Invalid options: '\\'' as quote char is shadowed by '\\'' as field divider");
+
+        assert!(Options::csv().validated().is_ok());
+    }
+
+    #[test]
+    fn options_should_clone_compare_and_hash() {
+        use std::collections::HashSet;
+
+        let options = Options::csv().has_header(true);
+        let copy = options.clone();
+        assert_eq!(options, copy);
+        assert_ne!(options, Options::csv());
+
+        let set = [Options::csv(), Options::tsv(), Options::csv()].into_iter().collect::<HashSet<_>>();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn options_should_build_from_parts() {
+        let mut record = RecordOpt::default();
+        record.field_div = vec!['|'];
+        let mut limits = Limits::default();
+        limits.depth = Some(2);
+
+        let mut options = Options::default();
+        options.record = record;
+        options.strings = Some(QuoteOpt::new(&['"'], None));
+        options.key_value = Some(KeyValueOpt::new(&['='], Duplicates::Last));
+        options.limits = limits;
+        options.has_header = true;
+
+        let expected = Options::default().field_dividers(&['|']).allow_strings(&['"']).key_values(&['=']).keep_last_duplicate_keys().max_depth(2).has_header(true);
+        assert_eq!(options, expected);
+        assert_eq!(options.record.record_div, Div::EndLine);
+        assert_eq!(options.spacing, Spacing::Drop);
+    }
+
+    #[test]
+    fn parse_records_should_apply_column_rules_by_index() {
+        let options = Options::default()
+            .column(Column::Index(1), &[ColumnRule::Raw(true), ColumnRule::Trim(Trim::Both)])
+            .column(Column::Index(2), &[ColumnRule::Numbers(Numbers::Decimal)]);
+        let output = parse_records(&mut "a b, free (text) 'x' ,-1.5e3 x".chars(), &options).unwrap();
+
+        let expected = Entry::List(vec![ record(vec![ field(vec![sym("a"), sym("b")])
+                                                    , field(vec![string("free (text) x")])
+                                                    , field(vec![Entry::Value(Value::Number("-1.5e3".into())), sym("x")])
+                                                    ])
+                                       ]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_records_should_apply_column_rules_by_name() {
+        let options = Options::default()
+            .has_header(true)
+            .allow_strings(&['"'])
+            .column(Column::Name("id".into()), &[ColumnRule::Numbers(Numbers::Off)])
+            .column(Column::Name("note".into()), &[ColumnRule::Quotes(vec!['\''])]);
+        let output = parse_records(&mut "id,note\n007,'a'\n1x,\"b\"".chars(), &options).unwrap();
+
+        let expected = Entry::List(vec![ record(vec![field(vec![string("id")]), field(vec![string("note")])])
+                                       , record(vec![field(vec![sym("007")]), field(vec![string("a")])])
+                                       , record(vec![field(vec![sym("1x")]), field(vec![punct('"'), sym("b"), punct('"')])])
+                                       ]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_records_should_report_unknown_column_name() {
+        let options = Options::default().has_header(true).column(Column::Name("price".into()), &[ColumnRule::Raw(true)]);
+        assert_eq!(parse_records(&mut "id,cost\n1,2".chars(), &options), Err("No column named 'price'".into()));
+
+        let options = Options::default().column(Column::Name("price".into()), &[ColumnRule::Raw(true)]);
+        assert_eq!(parse_records(&mut "1,2".chars(), &options), Err("No column named 'price'".into()));
+    }
+
+    #[test]
+    fn parse_records_should_trim_tokenized_fields() {
+        let options = Options::default().preserve_spacing_runs().trim(Trim::Start);
+        let output = parse_records(&mut "  a b  ,c".chars(), &options).unwrap();
+
+        let expected = Entry::List(vec![ record(vec![ field(vec![sym("a"), ws(" "), sym("b"), ws("  ")])
+                                                    , field(vec![sym("c")])
+                                                    ])
+                                       ]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_records_should_parse_raw_fields() {
+        let options = Options::csv().raw_fields(true);
+        let output = parse_records(&mut include_str!("../samples/rfc4180.csv").chars(), &options).unwrap();
+
+        let row = |xs : &[&str]| record(xs.iter().map(|x| field(vec![string(x)])).collect());
+        let expected = Entry::List(vec![ row(&["name", "quote", "note"])
+                                       , row(&["alice", "she said \"hi\"", ""])
+                                       , row(&["bob", "multi\r\nline", "a,b"])
+                                       , row(&[" carol ", " plain ", ""])
+                                       , row(&["", "", ""])
+                                       ]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_records_should_trim_raw_fields_outside_of_quotes() {
+        let options = Options::csv().raw_fields(true).trim(Trim::Both);
+        let output = parse_records(&mut "  a b ,  \" c \"  ,\t-1.5 ".chars(), &options).unwrap();
+
+        let expected = Entry::List(vec![ record(vec![ field(vec![string("a b")])
+                                                    , field(vec![string(" c ")])
+                                                    , field(vec![string("-1.5")])
+                                                    ])
+                                       ]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_records_should_match_raw_fields() {
+        let options = Options::csv().raw_fields(true);
+        let output = parse_records(&mut "x,1 + 2\ny,\"3, 4\"".chars(), &options).unwrap();
+
+        let record_pattern = plist_path(vec![
+                                precord(pexact_list(vec![ pfield(pexact_list(vec![Pattern::CaptureVar("a".into())]))
+                                                        , pfield(pexact_list(vec![Pattern::CaptureVar("b".into())]))
+                                                        ]))]);
+
+        let records = m(record_pattern, &output);
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].get("b").unwrap(), &&string("1 + 2"));
+        assert_eq!(records[1].get("b").unwrap(), &&string("3, 4"));
+    }
+
+    #[test]
+    fn parse_records_should_stop_raw_fields_at_key_value_separators() {
+        let options = Options::logfmt().raw_fields(true);
+        let output = parse_records(&mut "msg=\"a b\" n=a=b".chars(), &options).unwrap();
+
+        let expected = Entry::List(vec![ record(vec![ key_value_field(vec![string("msg")], vec![string("a b")])
+                                                    , key_value_field(vec![string("n")], vec![string("a=b")])
+                                                    ])
+                                       ]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn markdown_table_should_parse_pipe_table_with_alignment() {
+        let output = parse_document(&mut include_str!("../samples/table.md").chars(), &Options::markdown_table()).unwrap();
+
+        assert_eq!(output.header, Some(vec!["Name".to_string(), "Age".to_string(), "City".to_string()]));
+        assert_eq!(output.alignment, Some(vec![Align::Left, Align::Center, Align::Right]));
+
+        let rows = output.rows().collect::<Vec<_>>();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].get("Name").unwrap(), &field(vec![string("Ada Lovelace")]));
+        assert_eq!(rows[0].get("City").unwrap(), &field(vec![string("London")]));
+        assert_eq!(rows[1].get("Age").unwrap(), &field(vec![string("")]));
+    }
+
+    #[test]
+    fn markdown_table_should_parse_bordered_ascii_table() {
+        let output = parse_document(&mut include_str!("../samples/table.txt").chars(), &Options::markdown_table()).unwrap();
+
+        assert_eq!(output.header, Some(vec!["Name".to_string(), "Age".to_string()]));
+        assert_eq!(output.alignment, None);
+        assert_eq!(texts(&Entry::List(output.records)), vec![ vec!["Ada Lovelace", "36"]
+                                                            , vec!["Grace Hopper", "85"]
+                                                            ]);
+    }
+
+    #[test]
+    fn markdown_table_should_keep_escaped_pipes_in_cells() {
+        let output = parse_document(&mut "| a | b |\n|---|---|\n| x \\| y | 2 |\n| z\\|w\\ | 3 |".chars(), &Options::markdown_table()).unwrap();
+
+        assert_eq!(texts(&Entry::List(output.records)), vec![ vec!["x | y", "2"]
+                                                            , vec!["z|w\\", "3"]
+                                                            ]);
+    }
+
+    #[test]
+    fn markdown_table_should_keep_rows_of_dashes() {
+        let output = parse_document(&mut "| a | b |\n|---|:-:|\n| - | - |\n| 1 | -- |".chars(), &Options::markdown_table()).unwrap();
+
+        assert_eq!(output.alignment, Some(vec![Align::Default, Align::Center]));
+        assert_eq!(texts(&Entry::List(output.records)), vec![ vec!["-", "-"]
+                                                            , vec!["1", "--"]
+                                                            ]);
+    }
+
+    #[test]
+    fn parse_records_should_parse_tokenized_table_without_outer_dividers() {
+        let options = Options::default().field_dividers(&['|']).tables(true);
+        let output = parse_records(&mut "a | b\n---+---\n1 | 2 |  \n".chars(), &options).unwrap();
+
+        let expected = Entry::List(vec![ record(vec![field(vec![sym("a")]), field(vec![sym("b")])])
+                                       , record(vec![field(vec![Entry::Value(num(1))]), field(vec![Entry::Value(num(2))])])
+                                       ]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_document_should_slice_aligned_columns_at_header_words() {
+        let options = Options::default().aligned_columns(true).has_header(true).raw_fields(true).trim(Trim::Both).disallow_strings();
+        let output = parse_document(&mut include_str!("../samples/docker_ps.txt").chars(), &options).unwrap();
+
+        assert_eq!(output.header, Some(["CONTAINER ID", "IMAGE", "COMMAND", "STATUS", "NAMES"].iter().map(|x| x.to_string()).collect()));
+        assert_eq!(texts(&Entry::List(output.records)), vec![ vec!["4f2a9c1b7d3e", "nginx:latest", "\"nginx -g daemon\"", "Up 2 hours", "web"]
+                                                            , vec!["9b8c7d6e5f4a", "redis", "\"redis-server\"", "Exited (0) 3m", "cache"]
+                                                            ]);
+    }
+
+    #[test]
+    fn parse_document_should_fit_right_aligned_values_to_aligned_columns() {
+        let options = Options::default().aligned_columns(true).has_header(true).raw_fields(true).trim(Trim::Both);
+        let output = parse_document(&mut include_str!("../samples/ps_aux.txt").chars(), &options).unwrap();
+
+        let rows = output.rows().collect::<Vec<_>>();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].get("USER").unwrap().text(), "www-data");
+        assert_eq!(rows[1].get("PID").unwrap().text(), "12345");
+        assert_eq!(rows[1].get("%CPU").unwrap().text(), "12.5");
+        assert_eq!(rows[1].get("COMMAND").unwrap().text(), "nginx: worker process");
+    }
+
+    #[test]
+    fn parse_records_should_infer_aligned_columns_from_gaps() {
+        let options = Options::default().aligned_columns(true);
+        let output = parse_records(&mut include_str!("../samples/columns.txt").chars(), &options).unwrap();
+
+        let expected = Entry::List(vec![ record(vec![field(vec![sym("eth0")]), field(vec![sym("up")]), field(vec![Entry::Value(num(1500))])])
+                                       , record(vec![field(vec![sym("lo")]), field(vec![sym("up")]), field(vec![Entry::Value(num(65536))])])
+                                       , record(vec![field(vec![sym("wlan0")]), field(vec![sym("down")]), field(vec![Entry::Value(num(1500))])])
+                                       ]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn ini_should_nest_records_under_sections() {
+        let output = parse_records(&mut include_str!("../samples/config.ini").chars(), &Options::ini()).unwrap();
+
+        let kv = |k : &str, v : &str| record(vec![key_value_field(vec![string(k)], vec![string(v)])]);
+        let expected = Entry::List(vec![ kv("name", "demo")
+                                       , section(vec![string("core")], vec![ kv("editor", "vim")
+                                                                           , kv("bare", "false")
+                                                                           , kv("autocrlf", "input")
+                                                                           , kv("quoted", "q;r")
+                                                                           ])
+                                       , section(vec![string("remote origin")], vec![ kv("url", "https://example.com/repo.git")
+                                                                                    , kv("fetch", "+refs/heads/*:refs/remotes/origin/*")
+                                                                                    ])
+                                       ]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_records_should_find_sections_and_comments_after_custom_endlines() {
+        let options = Options::ini().endline(';').comments(&['#']);
+        let output = parse_records(&mut "a = 1;# note;[s];  ;b = 2".chars(), &options).unwrap();
+
+        let kv = |k : &str, v : &str| record(vec![key_value_field(vec![string(k)], vec![string(v)])]);
+        let expected = Entry::List(vec![ kv("a", "1")
+                                       , section(vec![string("s")], vec![kv("b", "2")])
+                                       ]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_records_should_match_sections() {
+        let options = Options::default().sections('[', ']').comments(&['#']);
+        let output = parse_records(&mut "# header\n1,2\n[a]\n3,4\n  # skipped\n[b c]\n5,6".chars(), &options).unwrap();
+
+        let section_pattern = plist_path(vec![
+                                Pattern::Cons { name: "Section".into(), params: vec![ Pattern::CaptureVar("name".into())
+                                                                                    , Pattern::CaptureVar("records".into())
+                                                                                    ] }]);
+
+        let sections = m(section_pattern, &output);
+
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].get("name").unwrap(), &&Entry::List(vec![sym("a")]));
+        assert_eq!(sections[1].get("name").unwrap(), &&Entry::List(vec![sym("b"), sym("c")]));
+        assert_eq!(sections[1].get("records").unwrap(), &&Entry::List(vec![record(vec![field(vec![Entry::Value(num(5))]), field(vec![Entry::Value(num(6))])])]));
+
+        let output = output.from_list().unwrap();
+        assert_eq!(output[0], record(vec![field(vec![Entry::Value(num(1))]), field(vec![Entry::Value(num(2))])]));
+    }
+
+    #[test]
+    fn parse_records_should_report_bad_section_headers() {
+        let options = Options::ini();
+        assert_eq!(parse_records(&mut "a = 1\n[core\nb = 2".chars(), &options), Err("Unterminated section header at line 2, column 1".into()));
+        assert_eq!(parse_records(&mut "  [core] x".chars(), &options), Err("Unexpected 'x' after section header at line 1, column 3".into()));
+        assert!(parse_records(&mut "[core] ; comment\n".chars(), &options).is_ok());
+    }
+
+    #[test]
+    fn combined_log_should_name_fields() {
+        let output = parse_document(&mut include_str!("../samples/access.log").chars(), &Options::combined_log()).unwrap();
+        let rows = output.rows().collect::<Vec<_>>();
+
+        let get = |i : usize, name : &str| rows[i].get(name).unwrap().text();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(get(0, "host"), "127.0.0.1");
+        assert_eq!(get(0, "ident"), "-");
+        assert_eq!(get(0, "user"), "frank");
+        assert_eq!(get(0, "time"), "10/Oct/2000:13:55:36 -0700");
+        assert_eq!(get(0, "request"), "GET /apache_pb.gif HTTP/1.0");
+        assert_eq!(get(0, "status"), "200");
+        assert_eq!(get(0, "bytes"), "2326");
+        assert_eq!(get(0, "referer"), "http://www.example.com/start.html");
+        assert_eq!(get(0, "user-agent"), "Mozilla/4.08 [en] (Win98; I ;Nav)");
+
+        assert_eq!(get(1, "request"), "POST /api/login?next=%2F HTTP/1.1");
+        assert_eq!(get(1, "bytes"), "-");
+        assert_eq!(get(1, "user-agent"), "curl/8.5.0 \"quoted\"");
+    }
+
+    #[test]
+    fn common_log_should_report_extra_fields() {
+        let input = "::1 - - [18/Oct/2026:07:27:00 +0000] \"GET / HTTP/1.1\" 200 512";
+        let output = parse_document(&mut input.chars(), &Options::common_log()).unwrap();
+        assert_eq!(output.rows().next().unwrap().get("time").unwrap().text(), "18/Oct/2026:07:27:00 +0000");
+
+        let output = parse_document(&mut include_str!("../samples/access.log").chars(), &Options::common_log());
+        assert_eq!(output, Err("Record 1 has 9 fields but the header has 7".into()));
+    }
+
+    #[test]
+    fn parse_records_should_parse_quote_pairs() {
+        let options = Options::default().disallow_strings().quote_pairs(&[('[', ']'), ('<', '>')]);
+        let output = parse_records(&mut "a [b, c] <d]>,\"e\"".chars(), &options).unwrap();
+
+        let expected = Entry::List(vec![ record(vec![ field(vec![sym("a"), string("b, c"), string("d]")])
+                                                    , field(vec![punct('"'), sym("e"), punct('"')])
+                                                    ])
+                                       ]);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_document_should_use_column_names() {
+        let options = Options::default().column_names(&["x", "y"]);
+        let output = parse_document(&mut "1,2\n3,4".chars(), &options).unwrap();
+        assert_eq!(output.header, Some(vec!["x".to_string(), "y".to_string()]));
+        assert_eq!(output.rows().nth(1).unwrap().get("y").unwrap(), &field(vec![Entry::Value(num(4))]));
+
+        // Names replace the header record
+        let options = Options::default().has_header(true).column_names(&["x", "y"]).column(Column::Name("y".into()), &[ColumnRule::Raw(true)]);
+        let output = parse_document(&mut "a,b\n1,2 3".chars(), &options).unwrap();
+        assert_eq!(output.records, vec![record(vec![field(vec![Entry::Value(num(1))]), field(vec![string("2 3")])])]);
+    }
+
+    #[test]
+    fn parse_records_should_parse_endlines_inside_groups_with_spacing_runs() {
+        let options = Options::default().brackets(&[('(', ')')]).preserve_spacing_runs();
+
+        let output = parse_records(&mut "(a\nb)".chars(), &options).unwrap();
+        let expected = Entry::List(vec![record(vec![field(vec![group('(', vec![Entry::List(vec![sym("a"), ws("\n"), sym("b")])], ')')])])]);
+        assert_eq!(output, expected);
+
+        let output = parse_records(&mut "(a\r\nb)".chars(), &options).unwrap();
+        let expected = Entry::List(vec![record(vec![field(vec![group('(', vec![Entry::List(vec![sym("a"), ws("\r"), ws("\n"), sym("b")])], ')')])])]);
+        assert_eq!(output, expected);
+
+        let options = Options::default().brackets(&[('(', ')')]).sub_field_dividers(&[&[';']]);
+        let output = parse_records(&mut "(a;\nb)".chars(), &options).unwrap();
+        let expected = Entry::List(vec![record(vec![field(vec![Entry::List(vec![group('(', vec![Entry::List(vec![sym("a"), punct(';'), sym("b")])], ')')])])])]);
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_records_should_keep_single_endlines_in_multi_line_records_with_spacing_runs() {
+        let options = Options::default().multi_line_records().preserve_spacing_runs();
+        let output = parse_records(&mut "a b\nc\n\nd".chars(), &options).unwrap();
+
+        let expected = Entry::List(vec![ record(vec![field(vec![sym("a"), ws(" "), sym("b"), ws("\n"), sym("c"), ws("\n")])])
+                                       , record(vec![field(vec![sym("d")])])
+                                       ]);
+        assert_eq!(output, expected);
+
+        let options = Options::default().multi_line_records().raw_fields(true);
+        let output = parse_records(&mut "a b\nc\n\nd".chars(), &options).unwrap();
+
+        let expected = Entry::List(vec![ record(vec![field(vec![string("a b\nc\n")])])
+                                       , record(vec![field(vec![string("d")])])
+                                       ]);
+        assert_eq!(output, expected);
+    }
+
+    fn texts(output : &Entry) -> Vec<Vec<String>> {
+        output.from_list().unwrap().iter()
+            .map(|r| r.from_record().unwrap()[0].from_list().unwrap().iter().map(|f| f.text()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn csv_should_parse_rfc4180_sample() {
+        let output = parse_records(&mut include_str!("../samples/rfc4180.csv").chars(), &Options::csv()).unwrap();
+
+        assert_eq!(texts(&output), vec![ vec!["name", "quote", "note"]
+                                       , vec!["alice", "she said \"hi\"", ""]
+                                       , vec!["bob", "multi\r\nline", "a,b"]
+                                       , vec![" carol ", " plain ", ""]
+                                       , vec!["", "", ""]
+                                       ]);
+    }
+
+    #[test]
+    fn parse_records_should_only_keep_trailing_empty_fields_and_crlf_endlines_when_asked() {
+        let output = parse_records(&mut "1,2,\n3\r\n".chars(), &Options::default().preserve_spacing_runs()).unwrap();
+        let expected = Entry::List(vec![ record(vec![field(vec![Entry::Value(num(1))]), field(vec![Entry::Value(num(2))])])
+                                       , record(vec![field(vec![Entry::Value(num(3)), ws("\r")])])
+                                       ]);
+        assert_eq!(output, expected);
+
+        let options = Options::default().preserve_spacing_runs().trailing_empty_fields(true).crlf_endlines(true);
+        let output = parse_records(&mut "1,2,\n3\r\n".chars(), &options).unwrap();
+        let expected = Entry::List(vec![ record(vec![field(vec![Entry::Value(num(1))]), field(vec![Entry::Value(num(2))]), field(vec![])])
+                                       , record(vec![field(vec![Entry::Value(num(3))])])
+                                       ]);
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn csv_should_allow_missing_final_line_break() {
+        let output = parse_records(&mut "a,b\r\nc,".chars(), &Options::csv()).unwrap();
+        assert_eq!(texts(&output), vec![vec!["a", "b"], vec!["c", ""]]);
+    }
+
+    #[test]
+    fn tsv_should_parse_sample() {
+        let output = parse_records(&mut include_str!("../samples/scores.tsv").chars(), &Options::tsv()).unwrap();
+
+        assert_eq!(texts(&output), vec![ vec!["id", "name", "score"]
+                                       , vec!["1", "Ada Lovelace", "9.5"]
+                                       , vec!["2", "", "7"]
+                                       ]);
+    }
+
+    #[test]
+    fn colon_separated_should_parse_passwd_sample() {
+        let output = parse_records(&mut include_str!("../samples/passwd").chars(), &Options::colon_separated()).unwrap();
+
+        assert_eq!(texts(&output), vec![ vec!["root", "x", "0", "0", "root", "/root", "/bin/bash"]
+                                       , vec!["nobody", "x", "65534", "65534", "nobody,,,", "/nonexistent", "/usr/sbin/nologin"]
+                                       , vec!["sync", "x", "4", "65534", "", "/bin", "/bin/sync"]
+                                       ]);
+    }
+
+    #[test]
+    fn logfmt_should_parse_sample() {
+        let output = parse_records(&mut include_str!("../samples/app.log").chars(), &Options::logfmt()).unwrap();
+        let records = output.from_list().unwrap();
+
+        let get = |i : usize, key : &str| records[i].lookup(key).unwrap().iter().map(|x| x.text()).collect::<Vec<_>>();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(get(0, "msg"), vec!["request done"]);
+        assert_eq!(get(0, "path"), vec!["/api/users"]);
+        assert_eq!(get(0, "duration"), vec!["12ms"]);
+        assert_eq!(get(1, "msg"), vec!["slow \"query\""]);
+        assert_eq!(get(1, "retry"), vec![""]);
+    }
+
+    #[test]
+    fn whitespace_columns_should_parse_ps_sample() {
+        let output = parse_records(&mut include_str!("../samples/ps.txt").chars(), &Options::whitespace_columns()).unwrap();
+
+        assert_eq!(texts(&output), vec![ vec!["PID", "TTY", "TIME", "CMD"]
+                                       , vec!["1", "?", "00:00:02", "systemd"]
+                                       , vec!["412", "pts/0", "00:00:00", "bash"]
+                                       ]);
+    }
+
+    #[test]
+    fn paragraphs_should_parse_sample() {
+        let output = parse_records(&mut include_str!("../samples/paragraphs.txt").chars(), &Options::paragraphs()).unwrap();
+
+        assert_eq!(texts(&output), vec![ vec!["name: alice", "age: 30"]
+                                       , vec!["name: bob"]
+                                       , vec!["name: carol", "age: 41"]
+                                       ]);
+    }
+
     #[test]
     fn parse_string_should_parse_string() {
         let mut input = "'string another'".chars();
-        let output = parse_string(&mut input, |_| false, |x| x == '\'').unwrap();
+        let output = parse_string(&mut input, |_| false, |x| x == '\'', None).unwrap();
         assert_eq!(output, Entry::Value(Value::String("string another".into())));
     }
 
     #[test]
     fn parse_string_should_escape() {
         let mut input = "'string \\\\ \\' another'".chars();
-        let output = parse_string(&mut input, |x| x == '\\', |x| x == '\'').unwrap();
+        let output = parse_string(&mut input, |x| x == '\\', |x| x == '\'', None).unwrap();
         assert_eq!(output, Entry::Value(Value::String("string \\ ' another".into())));
     }
 
     #[test]
     fn parse_should_should_drop_escape_for_other() {
         let mut input = "'string \\x another'".chars();
-        let output = parse_string(&mut input, |x| x == '\\', |x| x == '\'').unwrap();
+        let output = parse_string(&mut input, |x| x == '\\', |x| x == '\'', None).unwrap();
         assert_eq!(output, Entry::Value(Value::String("string \\x another".into())));
     }
 
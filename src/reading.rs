@@ -0,0 +1,114 @@
+
+use std::io::Read;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+pub fn detect_bom(bytes : &[u8]) -> Option<(Encoding, usize)> {
+    match bytes {
+        [0xEF, 0xBB, 0xBF, ..] => Some((Encoding::Utf8, 3)),
+        [0xFF, 0xFE, ..] => Some((Encoding::Utf16Le, 2)),
+        [0xFE, 0xFF, ..] => Some((Encoding::Utf16Be, 2)),
+        _ => None,
+    }
+}
+
+pub fn strip_bom(input : &str) -> &str {
+    input.strip_prefix('\u{feff}').unwrap_or(input)
+}
+
+pub fn read(mut reader : impl Read, encoding : Option<Encoding>) -> Result<String, String> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes).map_err(|e| format!("Failed to read input: {}", e))?;
+    decode(&bytes, encoding)
+}
+
+pub fn decode(bytes : &[u8], encoding : Option<Encoding>) -> Result<String, String> {
+    let (encoding, bytes) = match (encoding, detect_bom(bytes)) {
+        (Some(Encoding::Latin1), _) => (Encoding::Latin1, bytes),
+        (None, Some((bom, length))) => (bom, &bytes[length..]),
+        (Some(declared), Some((bom, length))) if declared == bom => (declared, &bytes[length..]),
+        (Some(declared), Some((bom, _))) => {
+            return Err(format!("Byte order mark for {:?} does not match declared encoding {:?}", bom, declared));
+        },
+        (Some(declared), None) => (declared, bytes),
+        (None, None) => (Encoding::Utf8, bytes),
+    };
+
+    match encoding {
+        Encoding::Utf8 => String::from_utf8(bytes.to_vec()).map_err(|e| format!("Invalid UTF-8 at byte {}", e.utf8_error().valid_up_to())),
+        Encoding::Utf16Le => decode_utf16(bytes, u16::from_le_bytes),
+        Encoding::Utf16Be => decode_utf16(bytes, u16::from_be_bytes),
+        Encoding::Latin1 => Ok(bytes.iter().map(|x| *x as char).collect()),
+    }
+}
+
+fn decode_utf16(bytes : &[u8], to_unit : fn([u8; 2]) -> u16) -> Result<String, String> {
+    if bytes.len() % 2 != 0 {
+        return Err(format!("Invalid UTF-16 input with odd length of {} bytes", bytes.len()));
+    }
+
+    let units = bytes.chunks(2).map(|x| to_unit([x[0], x[1]]));
+
+    let mut ret = String::with_capacity(bytes.len() / 2);
+    let mut index = 0;
+    for c in char::decode_utf16(units) {
+        match c {
+            Ok(c) => {
+                ret.push(c);
+                index += c.len_utf16() * 2;
+            },
+            Err(_) => { return Err(format!("Invalid UTF-16 at byte {}", index)); },
+        }
+    }
+
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode_should_strip_utf8_bom() {
+        let output = decode(b"\xEF\xBB\xBFa,b", None).unwrap();
+        assert_eq!(output, "a,b");
+
+        let output = decode(b"\xEF\xBB\xBFa,b", Some(Encoding::Utf8)).unwrap();
+        assert_eq!(output, "a,b");
+    }
+
+    #[test]
+    fn decode_should_decode_utf16() {
+        let output = decode(b"\xFF\xFEa\x00,\x00\xE9\x00", None).unwrap();
+        assert_eq!(output, "a,é");
+
+        let output = decode(b"\x00a\x00,\xD8\x3D\xDE\x00", Some(Encoding::Utf16Be)).unwrap();
+        assert_eq!(output, "a,\u{1F600}");
+    }
+
+    #[test]
+    fn decode_should_decode_latin1() {
+        let output = decode(b"caf\xE9,\xFF\xFE", Some(Encoding::Latin1)).unwrap();
+        assert_eq!(output, "café,ÿþ");
+    }
+
+    #[test]
+    fn decode_should_report_invalid_input() {
+        assert_eq!(decode(b"ab\xFFc", None), Err("Invalid UTF-8 at byte 2".into()));
+        assert_eq!(decode(b"\xFF\xFEa\x00b", None), Err("Invalid UTF-16 input with odd length of 3 bytes".into()));
+        assert_eq!(decode(b"a\x00\x00\xDC", Some(Encoding::Utf16Le)), Err("Invalid UTF-16 at byte 2".into()));
+        assert_eq!(decode(b"\xFF\xFEa\x00", Some(Encoding::Utf8)), Err("Byte order mark for Utf16Le does not match declared encoding Utf8".into()));
+    }
+
+    #[test]
+    fn strip_bom_should_strip_decoded_bom() {
+        assert_eq!(strip_bom("\u{feff}a,b"), "a,b");
+        assert_eq!(strip_bom("a,b"), "a,b");
+    }
+}
@@ -0,0 +1,300 @@
+
+use crate::data::*;
+
+const DIVIDERS : [char; 6] = [',', '\t', ';', '|', ':', ' '];
+const QUOTES : [char; 2] = ['"', '\''];
+
+#[derive(Debug)]
+pub struct Dialect {
+    pub options : Options,
+    // Between 0 and 1, how consistent the field counts are across the sampled records
+    pub confidence : f64,
+}
+
+pub fn sniff(sample : &str) -> Options {
+    sniff_dialect(sample).options
+}
+
+pub fn sniff_dialect(sample : &str) -> Dialect {
+    let quote = sniff_quote(sample);
+    let escape = quote.and_then(|q| sniff_escape(sample, q));
+    let lines = split_lines(sample, quote, escape);
+    let blocks = split_blocks(&lines);
+    let paragraphs = if blocks.len() >= 2 && blocks.iter().all(|x| x.len() >= 2) {
+        Some((confidence(&blocks.iter().map(|x| x.len()).collect::<Vec<_>>()), keyed_blocks(&blocks)))
+    }
+    else {
+        None
+    };
+
+    let lines = lines.iter().filter(|x| x.trim().len() != 0).cloned().collect::<Vec<_>>();
+
+    let best = DIVIDERS.iter()
+        .map(|d| (*d, lines.iter().map(|line| split_fields(line, *d, quote, escape).len()).collect::<Vec<_>>()))
+        .filter(|(_, counts)| mode(counts) > 1)
+        .map(|(d, counts)| (d, confidence(&counts)))
+        .fold(None, |best : Option<(char, f64)>, (d, c)| match best {
+            Some((_, b)) if b >= c => best,
+            _ => Some((d, c)),
+        });
+
+    // Blank lines between blocks of rows with the same shape are only spacing, so
+    // paragraphs need the same keys in each block or more consistency than any divider
+    match (paragraphs, best) {
+        (Some((c, keyed)), Some((_, b))) if !keyed && c <= b => { },
+        (Some((c, _)), _) => {
+            let options = with_quote(Options::default().multi_line_records().field_dividers(&['\n']).whitespace_lines_are_blank(true), quote, escape);
+            return Dialect { options, confidence: c };
+        },
+        (None, _) => { },
+    }
+
+    let (options, confidence) = match best {
+        Some((' ', c)) => (Options::default().field_dividers(&[' ']).collapse_dividers(true), c),
+        Some((d, c)) => (Options::default().field_dividers(&[d]), c),
+        None => (Options::default().field_dividers(&[]), confidence(&lines.iter().map(|_| 1).collect::<Vec<_>>()) / 2.0),
+    };
+
+    let has_header = match best {
+        Some((d, _)) => sniff_header(&lines, d, quote, escape),
+        None => false,
+    };
+
+    let options = with_quote(options.single_line_records().has_header(has_header), quote, escape);
+    Dialect { options, confidence }
+}
+
+fn with_quote(options : Options, quote : Option<char>, escape : Option<char>) -> Options {
+    match (quote, escape) {
+        (Some(q), Some(e)) => options.allow_strings_with_escape(&[q], e),
+        (Some(q), None) => options.allow_strings(&[q]),
+        (None, _) => options.disallow_strings(),
+    }
+}
+
+// Finds whether quotes inside of strings are escaped by doubling them or with a backslash
+fn sniff_escape(sample : &str, quote : char) -> Option<char> {
+    let cs = sample.chars().collect::<Vec<_>>();
+    let mut in_quote = false;
+    let mut i = 0;
+    while i < cs.len() {
+        match cs[i] {
+            '\\' if in_quote && cs.get(i + 1) == Some(&quote) => { return Some('\\'); },
+            c if c == quote && in_quote && cs.get(i + 1) == Some(&quote) => { return Some(quote); },
+            c if c == quote => { in_quote = !in_quote; },
+            _ => { },
+        }
+        i += 1;
+    }
+    None
+}
+
+fn sniff_quote(sample : &str) -> Option<char> {
+    let cs = sample.chars().collect::<Vec<_>>();
+    QUOTES.iter()
+        .map(|q| (*q, cs.iter().enumerate().filter(|(i, c)| *c == q && (*i == 0 || !cs[i - 1].is_alphanumeric())).count()))
+        .filter(|(_, count)| *count >= 2)
+        .max_by_key(|(_, count)| *count)
+        .map(|(q, _)| q)
+}
+
+// An escaped quote does not end a string, a doubled quote ends and restarts it
+fn split_lines(sample : &str, quote : Option<char>, escape : Option<char>) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    let mut in_quote = false;
+    let mut cs = sample.chars();
+    while let Some(c) = cs.next() {
+        match c {
+            c if in_quote && Some(c) == escape && Some(c) != quote => { 
+                line.push(c);
+                line.extend(cs.next());
+            },
+            '\n' if !in_quote => { lines.push(std::mem::replace(&mut line, String::new())); },
+            '\r' if !in_quote => { },
+            c if Some(c) == quote => { in_quote = !in_quote; line.push(c); },
+            c => { line.push(c); },
+        }
+    }
+    if line.len() != 0 {
+        lines.push(line);
+    }
+    lines
+}
+
+fn split_blocks(lines : &[String]) -> Vec<Vec<&String>> {
+    let mut blocks = vec![];
+    let mut block = vec![];
+    for line in lines {
+        if line.trim().len() == 0 {
+            if block.len() != 0 {
+                blocks.push(std::mem::replace(&mut block, vec![]));
+            }
+        }
+        else {
+            block.push(line);
+        }
+    }
+    if block.len() != 0 {
+        blocks.push(block);
+    }
+    blocks
+}
+
+// Whether each block starts its lines with the same words as the first block, ie name and age
+fn keyed_blocks(blocks : &[Vec<&String>]) -> bool {
+    let key = |line : &String| line.trim_start().split(|c : char| !c.is_alphanumeric() && c != '_' && c != '-').next().unwrap_or("").to_string();
+    let first = blocks[0].iter().map(|x| key(x)).collect::<Vec<_>>();
+    blocks[1..].iter().all(|block| block.iter().zip(&first).all(|(x, k)| key(x) == *k))
+}
+
+fn split_fields(line : &str, divider : char, quote : Option<char>, escape : Option<char>) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut in_quote = false;
+    let mut cs = line.chars();
+    while let Some(c) = cs.next() {
+        match c {
+            c if in_quote && Some(c) == escape && Some(c) != quote => { field.extend(cs.next()); },
+            c if Some(c) == quote => { in_quote = !in_quote; },
+            c if c == divider && !in_quote => { fields.push(std::mem::replace(&mut field, String::new())); },
+            c => { field.push(c); },
+        }
+    }
+    fields.push(field);
+
+    if divider == ' ' {
+        fields.into_iter().filter(|x| x.len() != 0).collect()
+    }
+    else {
+        fields
+    }
+}
+
+fn sniff_header(lines : &[String], divider : char, quote : Option<char>, escape : Option<char>) -> bool {
+    if lines.len() < 2 {
+        return false;
+    }
+
+    let is_number = |x : &str| x.trim().parse::<f64>().is_ok();
+
+    let header = split_fields(&lines[0], divider, quote, escape);
+    let rows = lines[1..].iter().map(|x| split_fields(x, divider, quote, escape)).collect::<Vec<_>>();
+
+    let mut votes = 0;
+    for (i, name) in header.iter().enumerate() {
+        let column = rows.iter().filter_map(|x| x.get(i)).collect::<Vec<_>>();
+        if column.len() == 0 {
+            continue;
+        }
+        if column.iter().all(|x| is_number(x)) {
+            votes += if is_number(name) { -1 } else { 1 };
+        }
+        else if column.iter().all(|x| x.chars().count() == column[0].chars().count()) {
+            votes += if name.chars().count() == column[0].chars().count() { -1 } else { 1 };
+        }
+    }
+
+    votes > 0
+}
+
+fn mode(counts : &[usize]) -> usize {
+    let mut best = (0, 0);
+    for count in counts {
+        let n = counts.iter().filter(|x| *x == count).count();
+        if n > best.1 || (n == best.1 && *count > best.0) {
+            best = (*count, n);
+        }
+    }
+    best.0
+}
+
+fn confidence(counts : &[usize]) -> f64 {
+    if counts.len() == 0 {
+        return 0.0;
+    }
+    let m = mode(counts);
+    let consistency = counts.iter().filter(|x| **x == m).count() as f64 / counts.len() as f64;
+    // Fewer samples earn less confidence
+    consistency * (1.0 - 1.0 / (counts.len() as f64 + 1.0))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::parsing::*;
+
+    #[test]
+    fn sniff_should_find_csv_with_header_and_doubled_quotes() {
+        let sample = "name,quote,score\nalice,\"she said \"\"hi\"\", twice\",10\nbob,\"\",7\ncarol,\"a,b\",3\n";
+        let dialect = sniff_dialect(sample);
+
+        assert_eq!(dialect.options.record.field_div, vec![',']);
+        assert_eq!(dialect.options.record.record_div, Div::EndLine);
+        assert_eq!(dialect.options.strings.as_ref().unwrap().quote_chars, vec!['"']);
+        assert_eq!(dialect.options.strings.as_ref().unwrap().escape_char, Some('"'));
+        assert!(dialect.options.has_header);
+        assert!(dialect.confidence > 0.7);
+
+        let output = parse_document(&mut sample.chars(), &dialect.options).unwrap();
+        let rows = output.rows().collect::<Vec<_>>();
+        assert_eq!(rows[0].get("quote").unwrap().text(), "she said \"hi\", twice");
+    }
+
+    #[test]
+    fn sniff_should_find_tsv_without_header() {
+        let options = sniff("1\t2\t3\n4\t5\t6\n7\t8\t9\n");
+
+        assert_eq!(options.record.field_div, vec!['\t']);
+        assert!(options.strings.is_none());
+        assert!(!options.has_header);
+    }
+
+    #[test]
+    fn sniff_should_find_backslash_escapes() {
+        let sample = "a|'it\\'s'|1\nb|'ok'|2\n";
+        assert_eq!(split_lines(sample, Some('\''), Some('\\')).len(), 2);
+        assert_eq!(split_fields("a|'x\\'|y'|1", '|', Some('\''), Some('\\')), vec!["a", "x'|y", "1"]);
+
+        let dialect = sniff_dialect(sample);
+        assert!(dialect.confidence > 0.6);
+
+        let options = dialect.options;
+
+        assert_eq!(options.record.field_div, vec!['|']);
+        assert_eq!(options.strings.as_ref().unwrap().quote_chars, vec!['\'']);
+        assert_eq!(options.strings.as_ref().unwrap().escape_char, Some('\\'));
+    }
+
+    #[test]
+    fn sniff_should_find_whitespace_columns() {
+        let options = sniff("PID  TTY   TIME\n  1  ?     0:01\n 22  pts/0 0:00\n");
+
+        assert_eq!(options.record.field_div, vec![' ']);
+        assert!(options.record.collapse_div);
+    }
+
+    #[test]
+    fn sniff_should_find_multi_line_records() {
+        let options = sniff("name alice\nage 30\n\nname bob\nage 41\n\nname carol\nage 3\n");
+
+        assert_eq!(options.record.record_div, Div::BlankLine);
+        assert_eq!(options.record.field_div, vec!['\n']);
+    }
+
+    #[test]
+    fn sniff_should_find_csv_with_blank_lines_between_rows() {
+        let dialect = sniff_dialect("a,b\nc,d\n\ne,f\ng,h\n");
+
+        assert_eq!(dialect.options.record.record_div, Div::EndLine);
+        assert_eq!(dialect.options.record.field_div, vec![',']);
+        assert!(dialect.confidence > 0.7);
+    }
+
+    #[test]
+    fn sniff_should_report_low_confidence_for_inconsistent_sample() {
+        let dialect = sniff_dialect("a,b\nc\nd,e,f,g\nh;i\n");
+        assert!(dialect.confidence < 0.5);
+    }
+}
@@ -0,0 +1,475 @@
+
+use std::path::Path;
+
+use crate::data::*;
+
+// A spec is a list of key=value entries separated by ';' or newlines, ie
+// sep=|;quote="';esc=\;records=blank;spacing=keep
+//
+// Whitespace around entries is ignored and lines starting with '#' are comments.
+// Chars which would otherwise be ambiguous are written by name, ie {tab}.  Char
+// lists are written as the chars themselves while string lists are separated by
+// ','.  An empty value turns the option off.  Column rules are written as
+// column.<index or name>.<rule>=value, ie column.price.numbers=decimal, with names
+// which would read as an index quoted, ie column."2024".raw=true
+
+const NAMES : [(&str, char); 10] = [ ("tab", '\t')
+                                   , ("space", ' ')
+                                   , ("newline", '\n')
+                                   , ("cr", '\r')
+                                   , ("semicolon", ';')
+                                   , ("comma", ',')
+                                   , ("hash", '#')
+                                   , ("equals", '=')
+                                   , ("lbrace", '{')
+                                   , ("rbrace", '}')
+                                   ];
+
+impl std::str::FromStr for Options {
+    type Err = String;
+
+    fn from_str(spec : &str) -> Result<Self, String> {
+        parse(spec)
+    }
+}
+
+impl std::fmt::Display for Options {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", to_spec(self))
+    }
+}
+
+pub fn load(path : impl AsRef<Path>) -> Result<Options, String> {
+    let spec = std::fs::read_to_string(path.as_ref()).map_err(|e| format!("Failed to read spec file {}: {}", path.as_ref().display(), e))?;
+    parse(&spec)
+}
+
+pub fn save(options : &Options, path : impl AsRef<Path>) -> Result<(), String> {
+    let spec = to_spec(options).replace(';', "\n") + "\n";
+    std::fs::write(path.as_ref(), spec).map_err(|e| format!("Failed to write spec file {}: {}", path.as_ref().display(), e))
+}
+
+pub fn parse(spec : &str) -> Result<Options, String> {
+    let mut options = Options::default();
+    for entry in spec.split(|c| c == ';' || c == '\n') {
+        let entry = entry.trim_start();
+        if entry.trim().len() == 0 || entry.starts_with('#') {
+            continue;
+        }
+        let (key, value) = entry.split_once('=').ok_or_else(|| format!("Missing '=' in spec entry '{}'", entry.trim()))?;
+        // Trimming would silently turn a literal tab or space into an empty value
+        if value.trim().len() == 0 && value.contains(|c| c == ' ' || c == '\t') {
+            return Err(format!("Invalid value for spec key '{}': spacing is trimmed, write it as {{tab}} or {{space}}", key.trim()));
+        }
+        let (key, value) = (key.trim(), value.trim());
+        apply(&mut options, key, value).map_err(|e| format!("Invalid value '{}' for spec key '{}': {}", value, key, e))?;
+    }
+    Ok(options)
+}
+
+fn apply(options : &mut Options, key : &str, value : &str) -> Result<(), String> {
+    if let Some(rest) = key.strip_prefix("column.") {
+        let (column, rule) = rest.rsplit_once('.').ok_or_else(|| "expected column.<column>.<rule>".to_string())?;
+        let quoted = column.strip_prefix('"').and_then(|x| x.strip_suffix('"'));
+        let column = match (quoted, column.parse()) {
+            (Some(name), _) => Column::Name(parse_chars(name)?.into_iter().collect()),
+            (None, Ok(index)) => Column::Index(index),
+            (None, Err(_)) => Column::Name(parse_chars(column)?.into_iter().collect()),
+        };
+        let rule = match rule {
+            "raw" => ColumnRule::Raw(parse_bool(value)?),
+            "quote" => ColumnRule::Quotes(parse_chars(value)?),
+            "esc" if value.len() == 0 => ColumnRule::Escape(None),
+            "esc" => ColumnRule::Escape(Some(parse_char(value)?)),
+            "numbers" => ColumnRule::Numbers(parse_numbers(value)?),
+            "trim" => ColumnRule::Trim(parse_trim(value)?),
+            _ => return Err("unknown column rule".into()),
+        };
+        options.columns.push((column, rule));
+        return Ok(());
+    }
+
+    match key {
+        "endline" => { options.endline = parse_char(value)?; },
+        "records" => {
+            options.record.record_div = match value {
+                "line" => Div::EndLine,
+                "blank" => Div::BlankLine,
+                _ => return Err("expected line or blank".into()),
+            };
+        },
+        "sep" => { options.record.field_div = parse_chars(value)?; },
+        "sub" => { options.record.sub_field_div = parse_list(value)?.iter().map(|x| x.chars().collect()).collect(); },
+        "collapse" => { options.record.collapse_div = parse_bool(value)?; },
+        "empty" => {
+            options.record.empty = match value {
+                "keep" => EmptyRecords::Keep,
+                "drop" => EmptyRecords::Drop,
+                "error" => EmptyRecords::Error,
+                _ => return Err("expected keep, drop or error".into()),
+            };
+        },
+        "blank_ws" => { options.record.blank_whitespace_lines = parse_bool(value)?; },
+        "trailing" => { options.record.trailing_empty_field = parse_bool(value)?; },
+        "crlf" => { options.record.crlf_endlines = parse_bool(value)?; },
+        "table" => { options.record.table = parse_bool(value)?; },
+        "aligned" => { options.record.aligned = parse_bool(value)?; },
+        "sections" => {
+            options.record.sections = match parse_chars(value)?[..] {
+                [] => None,
+                [open, close] => Some((open, close)),
+                _ => return Err("expected a pair of chars".into()),
+            };
+        },
+        "comments" => { options.comments = parse_chars(value)?; },
+        "quote" => {
+            let quote_chars = parse_chars(value)?;
+            options.strings = match (quote_chars.len(), options.strings.take()) {
+                (0, _) => None,
+                (_, Some(strings)) => Some(QuoteOpt { quote_chars, ..strings }),
+                (_, None) => Some(QuoteOpt { quote_chars, escape_char: None, pairs: vec![] }),
+            };
+        },
+        "quote_pairs" => {
+            let pairs = parse_pairs(value)?;
+            let strings = options.strings.take().unwrap_or(QuoteOpt { escape_char: None, quote_chars: vec![], pairs: vec![] });
+            options.strings = match (strings.quote_chars.len(), pairs.len()) {
+                (0, 0) => None,
+                _ => Some(QuoteOpt { pairs, ..strings }),
+            };
+        },
+        "esc" => {
+            let strings = options.strings.as_mut().ok_or_else(|| "strings are disabled, set quote first".to_string())?;
+            strings.escape_char = match value {
+                "" => None,
+                _ => Some(parse_char(value)?),
+            };
+        },
+        "spacing" => {
+            options.spacing = match value {
+                "drop" => Spacing::Drop,
+                "keep" => Spacing::Chars,
+                "runs" => Spacing::Runs,
+                "trivia" => Spacing::Trivia,
+                _ => return Err("expected drop, keep, runs or trivia".into()),
+            };
+        },
+        "brackets" => { options.brackets = parse_pairs(value)?; },
+        "operators" => { options.operators = parse_list(value)?; },
+        "chars" => {
+            options.chars = match value {
+                "standard" => CharClass::Standard,
+                "ascii" => CharClass::Ascii,
+                "unicode" => CharClass::Unicode,
+                _ => return Err("expected standard, ascii or unicode".into()),
+            };
+        },
+        "temporals" => { options.temporals = parse_bool(value)?; },
+        "trues" => { options.keywords.trues = parse_list(value)?; },
+        "falses" => { options.keywords.falses = parse_list(value)?; },
+        "nulls" => { options.keywords.nulls = parse_list(value)?; },
+        "keywords" => { options.keywords.others = parse_list(value)?; },
+        "keyword_case" => {
+            options.keywords.case_sensitive = match value {
+                "sensitive" => true,
+                "insensitive" => false,
+                _ => return Err("expected sensitive or insensitive".into()),
+            };
+        },
+        "header" => { options.has_header = parse_bool(value)?; },
+        "names" => { options.column_names = parse_list(value)?; },
+        "kv" => {
+            let separators = parse_chars(value)?;
+            options.key_value = match (separators.len(), options.key_value.take()) {
+                (0, _) => None,
+                (_, Some(kv)) => Some(KeyValueOpt { separators, ..kv }),
+                (_, None) => Some(KeyValueOpt { separators, duplicates: Duplicates::All }),
+            };
+        },
+        "duplicates" => {
+            let kv = options.key_value.as_mut().ok_or_else(|| "key values are disabled, set kv first".to_string())?;
+            kv.duplicates = match value {
+                "first" => Duplicates::First,
+                "last" => Duplicates::Last,
+                "all" => Duplicates::All,
+                "error" => Duplicates::Error,
+                _ => return Err("expected first, last, all or error".into()),
+            };
+        },
+        "raw" => { options.raw = parse_bool(value)?; },
+        "numbers" => { options.numbers = parse_numbers(value)?; },
+        "trim" => { options.trim = parse_trim(value)?; },
+        "max_string" => { options.limits.string_length = parse_limit(value)?; },
+        "max_values" => { options.limits.values_per_field = parse_limit(value)?; },
+        "max_fields" => { options.limits.fields_per_record = parse_limit(value)?; },
+        "max_records" => { options.limits.records = parse_limit(value)?; },
+        "max_depth" => { options.limits.depth = parse_limit(value)?; },
+        "max_input" => { options.limits.input_size = parse_limit(value)?; },
+        _ => return Err("unknown key".into()),
+    }
+    Ok(())
+}
+
+fn parse_numbers(value : &str) -> Result<Numbers, String> {
+    match value {
+        "off" => Ok(Numbers::Off),
+        "digits" => Ok(Numbers::Digits),
+        "decimal" => Ok(Numbers::Decimal),
+        _ => Err("expected off, digits or decimal".into()),
+    }
+}
+
+fn parse_trim(value : &str) -> Result<Trim, String> {
+    match value {
+        "keep" => Ok(Trim::Keep),
+        "start" => Ok(Trim::Start),
+        "end" => Ok(Trim::End),
+        "both" => Ok(Trim::Both),
+        _ => Err("expected keep, start, end or both".into()),
+    }
+}
+
+fn parse_pairs(value : &str) -> Result<Vec<(char, char)>, String> {
+    parse_list(value)?.iter().map(|x| match x.chars().collect::<Vec<_>>()[..] {
+        [open, close] => Ok((open, close)),
+        _ => Err(format!("expected a pair of chars but found '{}'", x)),
+    }).collect()
+}
+
+fn parse_bool(value : &str) -> Result<bool, String> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err("expected true or false".into()),
+    }
+}
+
+fn parse_limit(value : &str) -> Result<Option<usize>, String> {
+    match value {
+        "" => Ok(None),
+        _ => value.parse().map(Some).map_err(|_| "expected a number".to_string()),
+    }
+}
+
+fn parse_char(value : &str) -> Result<char, String> {
+    match parse_chars(value)?[..] {
+        [c] => Ok(c),
+        _ => Err("expected a single char".into()),
+    }
+}
+
+fn parse_chars(value : &str) -> Result<Vec<char>, String> {
+    let mut ret = vec![];
+    let mut cs = value.chars();
+    while let Some(c) = cs.next() {
+        match c {
+            '{' => {
+                let name = cs.by_ref().take_while(|x| *x != '}').collect::<String>();
+                let (_, c) = NAMES.iter().find(|(n, _)| *n == name).ok_or_else(|| format!("unknown char name '{{{}}}'", name))?;
+                ret.push(*c);
+            },
+            '}' => { return Err("unexpected '}', use {rbrace}".into()); },
+            c => { ret.push(c); },
+        }
+    }
+    Ok(ret)
+}
+
+fn parse_list(value : &str) -> Result<Vec<String>, String> {
+    if value.len() == 0 {
+        return Ok(vec![]);
+    }
+    value.split(',').map(|x| Ok(parse_chars(x)?.into_iter().collect())).collect()
+}
+
+pub fn to_spec(options : &Options) -> String {
+    let chars = |cs : &[char]| cs.iter().map(|c| escape(*c, false)).collect::<String>();
+    let list = |xs : &[String]| xs.iter().map(|x| x.chars().map(|c| escape(c, true)).collect::<String>()).collect::<Vec<_>>().join(",");
+    let bool = |x : bool| if x { "true" } else { "false" }.to_string();
+    let limit = |x : Option<usize>| x.map_or(String::new(), |x| x.to_string());
+
+    let mut entries = vec![];
+    entries.push(("endline", escape(options.endline, false)));
+    entries.push(("records", match options.record.record_div { Div::EndLine => "line", Div::BlankLine => "blank" }.to_string()));
+    entries.push(("sep", chars(&options.record.field_div)));
+    entries.push(("sub", list(&options.record.sub_field_div.iter().map(|x| x.iter().collect()).collect::<Vec<_>>())));
+    entries.push(("collapse", bool(options.record.collapse_div)));
+    entries.push(("empty", match options.record.empty { EmptyRecords::Keep => "keep", EmptyRecords::Drop => "drop", EmptyRecords::Error => "error" }.to_string()));
+    entries.push(("blank_ws", bool(options.record.blank_whitespace_lines)));
+    entries.push(("trailing", bool(options.record.trailing_empty_field)));
+    entries.push(("crlf", bool(options.record.crlf_endlines)));
+    entries.push(("table", bool(options.record.table)));
+    entries.push(("aligned", bool(options.record.aligned)));
+    entries.push(("sections", options.record.sections.map_or(String::new(), |(open, close)| chars(&[open, close]))));
+    entries.push(("comments", chars(&options.comments)));
+    match &options.strings {
+        Some(strings) if strings.quote_chars.len() != 0 || strings.pairs.len() != 0 => {
+            entries.push(("quote", chars(&strings.quote_chars)));
+            entries.push(("quote_pairs", list(&strings.pairs.iter().map(|(open, close)| format!("{}{}", open, close)).collect::<Vec<_>>())));
+            entries.push(("esc", strings.escape_char.map_or(String::new(), |c| escape(c, false))));
+        },
+        _ => { entries.push(("quote", String::new())); },
+    }
+    entries.push(("spacing", match options.spacing { Spacing::Drop => "drop", Spacing::Chars => "keep", Spacing::Runs => "runs", Spacing::Trivia => "trivia" }.to_string()));
+    entries.push(("brackets", list(&options.brackets.iter().map(|(open, close)| format!("{}{}", open, close)).collect::<Vec<_>>())));
+    entries.push(("operators", list(&options.operators)));
+    entries.push(("chars", match options.chars { CharClass::Standard => "standard", CharClass::Ascii => "ascii", CharClass::Unicode => "unicode" }.to_string()));
+    entries.push(("temporals", bool(options.temporals)));
+    entries.push(("trues", list(&options.keywords.trues)));
+    entries.push(("falses", list(&options.keywords.falses)));
+    entries.push(("nulls", list(&options.keywords.nulls)));
+    entries.push(("keywords", list(&options.keywords.others)));
+    entries.push(("keyword_case", if options.keywords.case_sensitive { "sensitive" } else { "insensitive" }.to_string()));
+    entries.push(("header", bool(options.has_header)));
+    entries.push(("names", list(&options.column_names)));
+    match &options.key_value {
+        Some(kv) => {
+            entries.push(("kv", chars(&kv.separators)));
+            entries.push(("duplicates", match kv.duplicates { Duplicates::First => "first", Duplicates::Last => "last", Duplicates::All => "all", Duplicates::Error => "error" }.to_string()));
+        },
+        None => { entries.push(("kv", String::new())); },
+    }
+    entries.push(("raw", bool(options.raw)));
+    entries.push(("numbers", numbers(options.numbers)));
+    entries.push(("trim", trim(options.trim)));
+    entries.push(("max_string", limit(options.limits.string_length)));
+    entries.push(("max_values", limit(options.limits.values_per_field)));
+    entries.push(("max_fields", limit(options.limits.fields_per_record)));
+    entries.push(("max_records", limit(options.limits.records)));
+    entries.push(("max_depth", limit(options.limits.depth)));
+    entries.push(("max_input", limit(options.limits.input_size)));
+
+    let mut entries = entries.into_iter().map(|(key, value)| format!("{}={}", key, value)).collect::<Vec<_>>();
+    for (column, rule) in &options.columns {
+        let column = match column {
+            Column::Index(index) => index.to_string(),
+            Column::Name(name) if name.parse::<usize>().is_ok() || name.starts_with('"') => format!("\"{}\"", name.chars().map(|c| escape(c, true)).collect::<String>()),
+            Column::Name(name) => name.chars().map(|c| escape(c, true)).collect(),
+        };
+        let (rule, value) = match rule {
+            ColumnRule::Raw(raw) => ("raw", bool(*raw)),
+            ColumnRule::Quotes(quote_chars) => ("quote", chars(quote_chars)),
+            ColumnRule::Escape(escape_char) => ("esc", escape_char.map_or(String::new(), |c| escape(c, false))),
+            ColumnRule::Numbers(x) => ("numbers", numbers(*x)),
+            ColumnRule::Trim(x) => ("trim", trim(*x)),
+        };
+        entries.push(format!("column.{}.{}={}", column, rule, value));
+    }
+    entries.join(";")
+}
+
+fn numbers(numbers : Numbers) -> String {
+    match numbers { Numbers::Off => "off", Numbers::Digits => "digits", Numbers::Decimal => "decimal" }.to_string()
+}
+
+fn trim(trim : Trim) -> String {
+    match trim { Trim::Keep => "keep", Trim::Start => "start", Trim::End => "end", Trim::Both => "both" }.to_string()
+}
+
+fn escape(c : char, in_list : bool) -> String {
+    match NAMES.iter().find(|(_, x)| *x == c) {
+        Some((name, _)) if (c != ',' && c != '=') || in_list => format!("{{{}}}", name),
+        _ => c.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_should_parse_example_spec() {
+        let output = parse("sep=|;quote=\"';esc=\\;records=blank;spacing=keep").unwrap();
+
+        let expected = Options::default()
+            .field_dividers(&['|'])
+            .allow_strings_with_escape(&['"', '\''], '\\')
+            .multi_line_records()
+            .preserve_spacing(true);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_should_parse_named_chars_and_lists() {
+        let output : Options = "sep={tab}{comma}; sub={semicolon},=:\n# comment\nbrackets=(),[]\noperators=->,{comma}\nkv=:\nduplicates=error\nmax_depth=4".parse().unwrap();
+
+        let expected = Options::default()
+            .field_dividers(&['\t', ','])
+            .sub_field_dividers(&[&[';'], &['=', ':']])
+            .brackets(&[('(', ')'), ('[', ']')])
+            .operators(&["->", ","])
+            .key_values(&[':'])
+            .reject_duplicate_keys()
+            .max_depth(4);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn to_spec_should_round_trip() {
+        let options = [ Options::default()
+                      , Options::csv().has_header(true)
+                      , Options::logfmt().keep_last_duplicate_keys()
+                      , Options::paragraphs().drop_empty_records()
+                      , Options::default().disallow_strings().brackets(&[('{', '}')]).operators(&[";", "=>"]).bool_keywords(&["yes"], &["no"])
+                                          .null_keywords(&["-"]).case_sensitive_keywords(false).unicode_chars().recognize_temporals(true)
+                                          .preserve_spacing_as_trivia().max_input_size(100).max_string_length(5)
+                      , Options::default().allow_strings(&['#']).endline('\r').field_dividers(&[' ', ';'])
+                      , Options::markdown_table()
+                      , Options::ini()
+                      , Options::combined_log()
+                      , Options::default().disallow_strings().quote_pairs(&[('<', '>')])
+                      , Options::default().aligned_columns(true).has_header(true)
+                      , Options::csv().raw_fields(true).trim(Trim::Both)
+                      , Options::csv().numbers(Numbers::Decimal).trim(Trim::End)
+                                      .column(Column::Index(2), &[ColumnRule::Raw(true), ColumnRule::Quotes(vec![]), ColumnRule::Escape(None)])
+                                      .column(Column::Name("unit price.eur=".into()), &[ColumnRule::Numbers(Numbers::Off), ColumnRule::Trim(Trim::Both)])
+                                      .column(Column::Name("2024".into()), &[ColumnRule::Raw(true)])
+                                      .column(Column::Name("\"q\"".into()), &[ColumnRule::Raw(true)])
+                                      .column(Column::Name("\"".into()), &[ColumnRule::Raw(true)])
+                      ];
+
+        for options in options {
+            let spec = options.to_string();
+            assert_eq!(spec.parse::<Options>().unwrap(), options, "{}", spec);
+        }
+    }
+
+    #[test]
+    fn parse_should_tell_quoted_column_names_from_indexes() {
+        let output = parse("column.2024.raw=true;column.\"2024\".raw=true").unwrap();
+        let expected = Options::default()
+            .column(Column::Index(2024), &[ColumnRule::Raw(true)])
+            .column(Column::Name("2024".into()), &[ColumnRule::Raw(true)]);
+
+        assert_eq!(output, expected);
+        assert!(to_spec(&expected).ends_with("column.2024.raw=true;column.\"2024\".raw=true"));
+    }
+
+    #[test]
+    fn parse_should_report_bad_key() {
+        assert_eq!(parse("sep=,;bogus=1"), Err("Invalid value '1' for spec key 'bogus': unknown key".into()));
+        assert_eq!(parse("sep=,;spacing"), Err("Missing '=' in spec entry 'spacing'".into()));
+        assert_eq!(parse("records=lines"), Err("Invalid value 'lines' for spec key 'records': expected line or blank".into()));
+        assert_eq!(parse("endline={nl}"), Err("Invalid value '{nl}' for spec key 'endline': unknown char name '{nl}'".into()));
+        assert_eq!(parse("quote=;esc=\\"), Err("Invalid value '\\' for spec key 'esc': strings are disabled, set quote first".into()));
+        assert_eq!(parse("max_records=many"), Err("Invalid value 'many' for spec key 'max_records': expected a number".into()));
+        assert_eq!(parse("sep=\t"), Err("Invalid value for spec key 'sep': spacing is trimmed, write it as {tab} or {space}".into()));
+        assert_eq!(parse("header=true;sep= "), Err("Invalid value for spec key 'sep': spacing is trimmed, write it as {tab} or {space}".into()));
+        assert_eq!(parse("sep=\r\nquote="), Ok(Options::default().field_dividers(&[]).disallow_strings()));
+    }
+
+    #[test]
+    fn load_should_read_saved_spec() {
+        let path = std::env::temp_dir().join(format!("record-spec-{}.txt", std::process::id()));
+        let options = Options::tsv().has_header(true);
+
+        save(&options, &path).unwrap();
+        let output = load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(output, options);
+        assert!(load(&path).unwrap_err().starts_with("Failed to read spec file"));
+    }
+}
@@ -0,0 +1,328 @@
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Temporal {
+    pub text : String,
+    pub kind : TemporalKind,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TemporalKind {
+    Date(Date),
+    Time(Time),
+    DateTime(Date, Time),
+    Duration(Duration),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Date {
+    pub year : u16,
+    pub month : u8,
+    pub day : u8,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Time {
+    pub hour : u8,
+    pub minute : u8,
+    pub second : u8,
+    pub nanosecond : u32,
+    // Minutes east of UTC
+    pub offset : Option<i16>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Duration {
+    pub years : u32,
+    pub months : u32,
+    pub weeks : u32,
+    pub days : u32,
+    pub hours : u32,
+    pub minutes : u32,
+    pub seconds : u32,
+    pub nanoseconds : u32,
+}
+
+pub(crate) fn recognize(input : &[char]) -> Option<(usize, Temporal)> {
+    let mut scanner = Scanner { input, index: 0 };
+    let kind = if input.first() == Some(&'P') {
+        TemporalKind::Duration(scanner.duration()?)
+    }
+    else {
+        match scanner.date() {
+            Some(date) if scanner.eat('T') => TemporalKind::DateTime(date, scanner.time()?),
+            Some(date) => TemporalKind::Date(date),
+            None => {
+                scanner.index = 0;
+                TemporalKind::Time(scanner.time()?)
+            },
+        }
+    };
+    let length = scanner.index;
+    Some((length, Temporal { text: input[..length].iter().collect(), kind }))
+}
+
+struct Scanner<'a> {
+    input : &'a [char],
+    index : usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn peek(&self) -> Option<char> {
+        self.input.get(self.index).copied()
+    }
+
+    fn eat(&mut self, c : char) -> bool {
+        if self.peek() == Some(c) {
+            self.index += 1;
+            true
+        }
+        else {
+            false
+        }
+    }
+
+    fn digits(&mut self, count : usize) -> Option<u32> {
+        let start = self.index;
+        let mut ret = 0;
+        for _ in 0..count {
+            match self.peek().and_then(|x| x.to_digit(10)) {
+                Some(d) => { ret = ret * 10 + d; self.index += 1; },
+                None => { self.index = start; return None; },
+            }
+        }
+        Some(ret)
+    }
+
+    fn number(&mut self) -> Option<u32> {
+        let start = self.index;
+        let mut ret : u32 = 0;
+        while let Some(d) = self.peek().and_then(|x| x.to_digit(10)) {
+            ret = ret.checked_mul(10)?.checked_add(d)?;
+            self.index += 1;
+        }
+        if self.index == start {
+            None
+        }
+        else {
+            Some(ret)
+        }
+    }
+
+    fn fraction(&mut self) -> Option<u32> {
+        let start = self.index;
+        if !(self.eat('.') || self.eat(',')) {
+            return Some(0);
+        }
+        let mut ret = 0;
+        let mut count = 0;
+        while let Some(d) = self.peek().and_then(|x| x.to_digit(10)) {
+            if count == 9 {
+                return None;
+            }
+            ret = ret * 10 + d;
+            count += 1;
+            self.index += 1;
+        }
+        if count == 0 {
+            self.index = start;
+            return Some(0);
+        }
+        Some(ret * 10u32.pow(9 - count))
+    }
+
+    fn date(&mut self) -> Option<Date> {
+        let start = self.index;
+        let ret = self.date_parts();
+        if ret.is_none() {
+            self.index = start;
+        }
+        ret
+    }
+
+    fn date_parts(&mut self) -> Option<Date> {
+        let year = self.digits(4)?;
+        if !self.eat('-') {
+            return None;
+        }
+        let month = self.digits(2)?;
+        if !self.eat('-') {
+            return None;
+        }
+        let day = self.digits(2)?;
+        if month < 1 || month > 12 || day < 1 || day > days_in_month(year, month) {
+            return None;
+        }
+        Some(Date { year: year as u16, month: month as u8, day: day as u8 })
+    }
+
+    fn time(&mut self) -> Option<Time> {
+        let hour = self.digits(2)?;
+        if !self.eat(':') {
+            return None;
+        }
+        let minute = self.digits(2)?;
+        let (second, nanosecond) = if self.eat(':') {
+            (self.digits(2)?, self.fraction()?)
+        }
+        else {
+            (0, 0)
+        };
+        if hour > 23 || minute > 59 || second > 60 {
+            return None;
+        }
+        let offset = self.offset();
+        Some(Time { hour: hour as u8, minute: minute as u8, second: second as u8, nanosecond, offset })
+    }
+
+    fn offset(&mut self) -> Option<i16> {
+        let start = self.index;
+        let ret = self.offset_parts();
+        if ret.is_none() {
+            self.index = start;
+        }
+        ret
+    }
+
+    fn offset_parts(&mut self) -> Option<i16> {
+        let sign = match self.peek() {
+            Some('Z') => { self.index += 1; return Some(0); },
+            Some('+') => 1,
+            Some('-') => -1,
+            _ => return None,
+        };
+        self.index += 1;
+        let hour = self.digits(2)?;
+        let minute = if self.eat(':') {
+            self.digits(2)?
+        }
+        else {
+            self.digits(2).unwrap_or(0)
+        };
+        if hour > 23 || minute > 59 {
+            return None;
+        }
+        Some(sign * (hour * 60 + minute) as i16)
+    }
+
+    fn duration(&mut self) -> Option<Duration> {
+        if !self.eat('P') {
+            return None;
+        }
+        let mut ret = Duration::default();
+        let mut components = 0;
+        for designator in ['Y', 'M', 'W', 'D'] {
+            if let Some(value) = self.component(designator) {
+                match designator {
+                    'Y' => ret.years = value,
+                    'M' => ret.months = value,
+                    'W' => ret.weeks = value,
+                    _ => ret.days = value,
+                }
+                components += 1;
+            }
+        }
+        if self.eat('T') {
+            let mut time_components = 0;
+            for designator in ['H', 'M'] {
+                if let Some(value) = self.component(designator) {
+                    match designator {
+                        'H' => ret.hours = value,
+                        _ => ret.minutes = value,
+                    }
+                    time_components += 1;
+                }
+            }
+            let start = self.index;
+            if let Some(seconds) = self.number() {
+                match self.fraction() {
+                    Some(nanoseconds) if self.eat('S') => {
+                        ret.seconds = seconds;
+                        ret.nanoseconds = nanoseconds;
+                        time_components += 1;
+                    },
+                    _ => { self.index = start; },
+                }
+            }
+            if time_components == 0 {
+                return None;
+            }
+            components += time_components;
+        }
+        if components == 0 {
+            None
+        }
+        else {
+            Some(ret)
+        }
+    }
+
+    fn component(&mut self, designator : char) -> Option<u32> {
+        let start = self.index;
+        match self.number() {
+            Some(value) if self.eat(designator) => Some(value),
+            _ => { self.index = start; None },
+        }
+    }
+}
+
+fn days_in_month(year : u32, month : u32) -> u32 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn r(input : &str) -> Option<(usize, Temporal)> {
+        recognize(&input.chars().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn recognize_should_recognize_date_time_with_offset() {
+        let (length, output) = r("2026-10-18T07:27:00.25+05:30 rest").unwrap();
+        assert_eq!(length, 28);
+        assert_eq!(output.text, "2026-10-18T07:27:00.25+05:30");
+        assert_eq!(output.kind, TemporalKind::DateTime( Date { year: 2026, month: 10, day: 18 }
+                                                      , Time { hour: 7, minute: 27, second: 0, nanosecond: 250_000_000, offset: Some(330) }));
+
+        let (_, output) = r("2026-10-18T07:27:00Z").unwrap();
+        assert_eq!(output.kind, TemporalKind::DateTime( Date { year: 2026, month: 10, day: 18 }
+                                                      , Time { hour: 7, minute: 27, second: 0, nanosecond: 0, offset: Some(0) }));
+    }
+
+    #[test]
+    fn recognize_should_recognize_date_and_time() {
+        let (length, output) = r("2024-02-29,").unwrap();
+        assert_eq!(length, 10);
+        assert_eq!(output.kind, TemporalKind::Date(Date { year: 2024, month: 2, day: 29 }));
+
+        let (length, output) = r("10:15:30").unwrap();
+        assert_eq!(length, 8);
+        assert_eq!(output.kind, TemporalKind::Time(Time { hour: 10, minute: 15, second: 30, nanosecond: 0, offset: None }));
+    }
+
+    #[test]
+    fn recognize_should_recognize_duration() {
+        let (length, output) = r("P1Y2M3DT4H5M6.5S").unwrap();
+        assert_eq!(length, 16);
+        assert_eq!(output.kind, TemporalKind::Duration(Duration { years: 1, months: 2, days: 3, hours: 4, minutes: 5, seconds: 6, nanoseconds: 500_000_000, ..Duration::default() }));
+
+        let (_, output) = r("P2W").unwrap();
+        assert_eq!(output.kind, TemporalKind::Duration(Duration { weeks: 2, ..Duration::default() }));
+    }
+
+    #[test]
+    fn recognize_should_reject_invalid() {
+        assert_eq!(r("2023-02-29"), None);
+        assert_eq!(r("2026-13-01"), None);
+        assert_eq!(r("24:00:00"), None);
+        assert_eq!(r("P"), None);
+        assert_eq!(r("PT"), None);
+        assert_eq!(r("2026"), None);
+    }
+}